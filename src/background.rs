@@ -0,0 +1,92 @@
+// Copyright (C) 2024 Adrien Bustany <adrien@bustany.org>
+
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+
+/// Background painted behind the pages, in SVG files and thumbnails.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Background {
+    /// An opaque color, given by its 8-bit RGB components.
+    Color(u8, u8, u8),
+    /// No background. Thumbnails then need a format supporting transparency.
+    Transparent,
+}
+
+impl Background {
+    pub const WHITE: Self = Self::Color(255, 255, 255);
+    pub const BLACK: Self = Self::Color(0, 0, 0);
+
+    /// Paints the background over the whole clip area of `ctx`.
+    pub(crate) fn paint(&self, ctx: &cairo::Context) -> Result<(), cairo::Error> {
+        let Self::Color(r, g, b) = *self else {
+            return Ok(());
+        };
+
+        ctx.save()?;
+        ctx.set_source_rgb(
+            f64::from(r) / 255.,
+            f64::from(g) / 255.,
+            f64::from(b) / 255.,
+        );
+        ctx.paint()?;
+        ctx.restore()
+    }
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Self::WHITE
+    }
+}
+
+/// Parses `transparent`, `white`, `black`, or a hex color such as `#336699`
+/// or `#369`.
+impl FromStr for Background {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "transparent" => return Ok(Self::Transparent),
+            "white" => return Ok(Self::WHITE),
+            "black" => return Ok(Self::BLACK),
+            _ => {}
+        }
+
+        let invalid = || anyhow!("invalid color \"{}\"", s);
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+
+        let component = |i: usize, len: usize| -> Result<u8> {
+            let v = u8::from_str_radix(&hex[i * len..(i + 1) * len], 16).map_err(|_| invalid())?;
+            // #369 is a short form of #336699
+            Ok(if len == 1 { v * 17 } else { v })
+        };
+
+        match hex.len() {
+            3 => Ok(Self::Color(
+                component(0, 1)?,
+                component(1, 1)?,
+                component(2, 1)?,
+            )),
+            6 => Ok(Self::Color(
+                component(0, 2)?,
+                component(1, 2)?,
+                component(2, 2)?,
+            )),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for Background {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Color(r, g, b) => write!(f, "#{:02x}{:02x}{:02x}", r, g, b),
+            Self::Transparent => write!(f, "transparent"),
+        }
+    }
+}
@@ -0,0 +1,246 @@
+// Copyright (C) 2024 Adrien Bustany <adrien@bustany.org>
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::geometry::PageArea;
+use crate::output::{to_hex, write_json};
+use crate::{
+    ConvertOptions, PageInfo, PageOutput, RasterImage, Thumbnail, ThumbnailSize, VectorFile,
+    VectorFormat,
+};
+
+const CACHE_FILENAME: &str = ".pdf2svgslides-cache.json";
+
+/// Record of the files generated by previous runs in an output directory,
+/// along with a hash of what they were generated from, so that unchanged
+/// pages do not get written again.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Cache {
+    #[serde(skip)]
+    output_dir: PathBuf,
+    // indexed by output name (see FilenameTemplate)
+    pages: BTreeMap<String, Entry>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Entry {
+    key: String,
+    svg: Option<FileStamp>,
+    #[serde(default)]
+    vector_files: Vec<VectorStamp>,
+    #[serde(default)]
+    thumbnails: Vec<ThumbnailStamp>,
+    #[serde(default)]
+    raster: Option<ImageStamp>,
+}
+
+/// Identifies a file as it was when last written, so that files modified or
+/// deleted since then get regenerated.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct FileStamp {
+    name: String,
+    size: u64,
+    modified_ns: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct ThumbnailStamp {
+    #[serde(flatten)]
+    file: FileStamp,
+    size: ThumbnailSize,
+    density: u32,
+    width: u32,
+    height: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct VectorStamp {
+    #[serde(flatten)]
+    file: FileStamp,
+    format: VectorFormat,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct ImageStamp {
+    #[serde(flatten)]
+    file: FileStamp,
+    width: u32,
+    height: u32,
+}
+
+impl Cache {
+    /// Loads the cache of `output_dir`. A missing or unreadable cache is
+    /// treated as empty, which just means that every page gets regenerated.
+    pub(crate) fn load(output_dir: &Path) -> Self {
+        let cache = std::fs::read(output_dir.join(CACHE_FILENAME))
+            .ok()
+            .and_then(|data| serde_json::from_slice::<Cache>(&data).ok())
+            .unwrap_or_default();
+
+        Self {
+            output_dir: output_dir.to_owned(),
+            ..cache
+        }
+    }
+
+    pub(crate) fn save(&self) -> Result<()> {
+        write_json(&self.output_dir.join(CACHE_FILENAME), self).context("error writing cache")
+    }
+
+    /// Returns the outputs recorded for a page, if they were generated from
+    /// the same `key` and were not modified since.
+    pub(crate) fn lookup(&self, name: &str, key: &str, page: &PageInfo) -> Option<PageOutput> {
+        let entry = self.pages.get(name).filter(|entry| entry.key == key)?;
+        let svg_path = match &entry.svg {
+            Some(stamp) => Some(self.check(stamp)?),
+            None => None,
+        };
+        let vector_files = entry
+            .vector_files
+            .iter()
+            .map(|stamp| {
+                Some(VectorFile {
+                    format: stamp.format,
+                    path: self.check(&stamp.file)?,
+                })
+            })
+            .collect::<Option<_>>()?;
+        let thumbnails = entry
+            .thumbnails
+            .iter()
+            .map(|stamp| {
+                Some(Thumbnail {
+                    path: self.check(&stamp.file)?,
+                    size: stamp.size,
+                    density: stamp.density,
+                    width: stamp.width,
+                    height: stamp.height,
+                })
+            })
+            .collect::<Option<_>>()?;
+        let raster = match &entry.raster {
+            Some(stamp) => Some(RasterImage {
+                path: self.check(&stamp.file)?,
+                width: stamp.width,
+                height: stamp.height,
+            }),
+            None => None,
+        };
+
+        Some(PageOutput {
+            page: page.clone(),
+            svg_path,
+            vector_files,
+            thumbnails,
+            raster,
+            regenerated: false,
+        })
+    }
+
+    /// Records the outputs generated for a page from `key`.
+    pub(crate) fn insert(&mut self, name: &str, key: String, output: &PageOutput) -> Result<()> {
+        let svg = output.svg_path.as_deref().map(stamp).transpose()?;
+        let vector_files = output
+            .vector_files
+            .iter()
+            .map(|file| -> Result<_> {
+                Ok(VectorStamp {
+                    file: stamp(&file.path)?,
+                    format: file.format,
+                })
+            })
+            .collect::<Result<_>>()?;
+        let thumbnails = output
+            .thumbnails
+            .iter()
+            .map(|thumbnail| -> Result<_> {
+                Ok(ThumbnailStamp {
+                    file: stamp(&thumbnail.path)?,
+                    size: thumbnail.size,
+                    density: thumbnail.density,
+                    width: thumbnail.width,
+                    height: thumbnail.height,
+                })
+            })
+            .collect::<Result<_>>()?;
+        let raster = output
+            .raster
+            .as_ref()
+            .map(|raster| -> Result<_> {
+                Ok(ImageStamp {
+                    file: stamp(&raster.path)?,
+                    width: raster.width,
+                    height: raster.height,
+                })
+            })
+            .transpose()?;
+
+        self.pages.insert(
+            name.to_owned(),
+            Entry {
+                key,
+                svg,
+                vector_files,
+                thumbnails,
+                raster,
+            },
+        );
+
+        Ok(())
+    }
+
+    fn check(&self, expected: &FileStamp) -> Option<PathBuf> {
+        let path = self.output_dir.join(&expected.name);
+        let actual = stamp(&path).ok()?;
+        (actual == *expected).then_some(path)
+    }
+}
+
+fn stamp(path: &Path) -> Result<FileStamp> {
+    let metadata =
+        std::fs::metadata(path).with_context(|| format!("error accessing {}", path.display()))?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    Ok(FileStamp {
+        name,
+        size: metadata.len(),
+        modified_ns: u64::try_from(modified.as_nanos()).unwrap_or(u64::MAX),
+    })
+}
+
+/// Computes the cache key of a page, from the hash of its contents (see
+/// [`content::page_hashes`](crate::content::page_hashes)) and from every
+/// option that can change the outputs.
+pub(crate) fn page_key(options: &ConvertOptions, area: &PageArea, content_hash: &str) -> String {
+    // options that do not change how an individual page gets rendered
+    let options = ConvertOptions {
+        output_dir: PathBuf::new(),
+        pages: Default::default(),
+        password: None,
+        jobs: 0,
+        incremental: false,
+        ..options.clone()
+    };
+
+    let mut hasher = Sha256::new();
+    hasher.update(env!("CARGO_PKG_VERSION"));
+    hasher.update(format!("{:?}", options));
+    hasher.update(format!("{:?}", area));
+    hasher.update(content_hash);
+
+    to_hex(&hasher.finalize())
+}
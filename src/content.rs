@@ -0,0 +1,250 @@
+// Copyright (C) 2024 Adrien Bustany <adrien@bustany.org>
+
+// Poppler does not give access to the objects of the document, so they are
+// read with lopdf, which only needs to parse the document, not to render it.
+
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use sha2::{Digest, Sha256};
+
+use crate::geometry::Rect;
+use crate::output::to_hex;
+
+/// Page attributes inherited from the page tree when a page does not set
+/// them.
+const INHERITED_KEYS: [&[u8]; 4] = [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+/// Attributes of a page that poppler does not expose.
+#[derive(Clone, Debug)]
+pub(crate) struct PageObjects {
+    /// Clockwise rotation of the page, in degrees: 0, 90, 180 or 270.
+    pub rotation: u16,
+    pub media_box: Rect,
+    /// Crop box, clipped to the media box as poppler does.
+    pub crop_box: Rect,
+    /// Trim box, defaulting to the crop box. Not clipped.
+    pub trim_box: Rect,
+    /// Hash of the PDF objects the page is drawn from, if requested.
+    pub hash: Option<String>,
+}
+
+/// Reads the attributes of every page of a document, along with the hashes
+/// of the PDF objects each page is drawn from if `hash` is set: its content
+/// streams, resources, annotations and attributes. Unlike the rendered
+/// outputs, the hashes only change when the page itself changes in the
+/// document.
+///
+/// Returns `None` if the document cannot be parsed, or if it does not have
+/// `page_count` pages as poppler sees them.
+pub(crate) fn read_pages(
+    data: &[u8],
+    password: Option<&str>,
+    page_count: i32,
+    hash: bool,
+) -> Option<Vec<PageObjects>> {
+    // lopdf panics on some malformed documents that poppler can still
+    // render, which must not stop the conversion
+    panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut doc = Document::load_mem(data).ok()?;
+        // documents only needing the empty user password get decrypted when
+        // loaded
+        if doc.is_encrypted() {
+            doc.decrypt(password?).ok()?;
+        }
+
+        let pages = doc.get_pages();
+        if i32::try_from(pages.len()).ok()? != page_count {
+            return None;
+        }
+
+        Some(
+            pages
+                .values()
+                .map(|&id| read_page(&doc, id, hash))
+                .collect(),
+        )
+    }))
+    .ok()
+    .flatten()
+}
+
+fn read_page(doc: &Document, id: ObjectId, hash: bool) -> PageObjects {
+    let mut page = doc.get_dictionary(id).cloned().unwrap_or_default();
+    page.remove(b"Parent");
+
+    for key in INHERITED_KEYS {
+        if !page.has(key) {
+            if let Some(value) = inherited(doc, id, key) {
+                page.set(key, value.clone());
+            }
+        }
+    }
+
+    // same defaults and normalization as poppler, pages without a media
+    // box being US Letter
+    let media_box = rect(doc, &page, b"MediaBox").unwrap_or(Rect {
+        x1: 0.,
+        y1: 0.,
+        x2: 612.,
+        y2: 792.,
+    });
+    let crop_box = rect(doc, &page, b"CropBox")
+        .and_then(|crop_box| crop_box.intersect(&media_box))
+        .unwrap_or(media_box);
+    let trim_box = rect(doc, &page, b"TrimBox").unwrap_or(crop_box);
+    let rotation = match page
+        .get(b"Rotate")
+        .and_then(|rotate| resolve(doc, rotate).as_i64())
+        .map_or(0, |rotate| rotate.rem_euclid(360))
+    {
+        rotation @ (90 | 180 | 270) => rotation as u16,
+        _ => 0,
+    };
+
+    let hash = hash.then(|| {
+        let mut hasher = PageHasher {
+            doc,
+            hasher: Sha256::new(),
+            visited: HashMap::from([(id, 0)]),
+        };
+        hasher.dictionary(&page);
+
+        to_hex(&hasher.hasher.finalize())
+    });
+
+    PageObjects {
+        rotation,
+        media_box,
+        crop_box,
+        trim_box,
+        hash,
+    }
+}
+
+/// Reads a rectangle attribute of a page, normalized so that its first
+/// corner is the bottom-left one.
+fn rect(doc: &Document, page: &Dictionary, key: &[u8]) -> Option<Rect> {
+    let values = resolve(doc, page.get(key).ok()?).as_array().ok()?;
+    let [x1, y1, x2, y2] = values
+        .iter()
+        .map(|value| resolve(doc, value).as_float().map(f64::from))
+        .collect::<Result<Vec<_>, _>>()
+        .ok()?
+        .try_into()
+        .ok()?;
+
+    Some(Rect {
+        x1: x1.min(x2),
+        y1: y1.min(y2),
+        x2: x1.max(x2),
+        y2: y1.max(y2),
+    })
+}
+
+fn resolve<'a>(doc: &'a Document, object: &'a Object) -> &'a Object {
+    doc.dereference(object).map_or(object, |(_, object)| object)
+}
+
+/// Looks up an attribute in the ancestors of a page.
+fn inherited<'a>(doc: &'a Document, page: ObjectId, key: &[u8]) -> Option<&'a Object> {
+    let mut node = doc.get_dictionary(page).ok()?;
+
+    // bounded, in case of a cycle in the page tree
+    for _ in 0..64 {
+        let parent = node.get(b"Parent").and_then(Object::as_reference).ok()?;
+        node = doc.get_dictionary(parent).ok()?;
+
+        if let Ok(value) = node.get(key) {
+            return Some(value);
+        }
+    }
+
+    None
+}
+
+/// Feeds objects to a hash, following references. Each referenced object is
+/// hashed once, later references to it being hashed by order of first
+/// visit, so that the hash does not depend on object numbers.
+struct PageHasher<'a> {
+    doc: &'a Document,
+    hasher: Sha256,
+    visited: HashMap<ObjectId, usize>,
+}
+
+impl PageHasher<'_> {
+    fn object(&mut self, object: &Object) {
+        match object {
+            Object::Null => self.tag(b'n'),
+            Object::Boolean(v) => self.bytes(b'b', &[u8::from(*v)]),
+            Object::Integer(v) => self.bytes(b'i', &v.to_le_bytes()),
+            Object::Real(v) => self.bytes(b'r', &v.to_bits().to_le_bytes()),
+            Object::Name(name) => self.bytes(b'/', name),
+            Object::String(s, _) => self.bytes(b's', s),
+            Object::Array(items) => {
+                self.bytes(b'[', &items.len().to_le_bytes());
+                for item in items {
+                    self.object(item);
+                }
+            }
+            Object::Dictionary(dict) => self.dictionary(dict),
+            Object::Stream(stream) => {
+                self.dictionary(&stream.dict);
+                self.bytes(b'S', &stream.content);
+            }
+            Object::Reference(id) => self.reference(*id),
+        }
+    }
+
+    fn dictionary(&mut self, dict: &Dictionary) {
+        let mut entries: Vec<_> = dict.iter().collect();
+        entries.sort_by_key(|&(key, _)| key);
+
+        self.bytes(b'<', &entries.len().to_le_bytes());
+        for (key, value) in entries {
+            self.bytes(b'/', key);
+            self.object(value);
+        }
+    }
+
+    fn reference(&mut self, id: ObjectId) {
+        if let Some(&n) = self.visited.get(&id) {
+            self.bytes(b'R', &n.to_le_bytes());
+            return;
+        }
+
+        let n = self.visited.len();
+        self.visited.insert(id, n);
+        self.bytes(b'O', &n.to_le_bytes());
+
+        let Ok(object) = self.doc.get_object(id) else {
+            return self.tag(b'n');
+        };
+
+        // other pages, e.g. the destinations of links, do not change how
+        // this page renders
+        let is_page = object
+            .as_dict()
+            .and_then(|dict| dict.get(b"Type"))
+            .and_then(Object::as_name)
+            .is_ok_and(|name| name == b"Page" || name == b"Pages");
+        if is_page {
+            return self.tag(b'p');
+        }
+
+        self.object(object);
+    }
+
+    fn tag(&mut self, tag: u8) {
+        self.hasher.update([tag]);
+    }
+
+    /// Hashes a tagged, length-prefixed value, so that consecutive values
+    /// cannot be confused.
+    fn bytes(&mut self, tag: u8, bytes: &[u8]) {
+        self.tag(tag);
+        self.hasher.update(bytes.len().to_le_bytes());
+        self.hasher.update(bytes);
+    }
+}
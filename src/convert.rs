@@ -0,0 +1,966 @@
+// Copyright (C) 2024 Adrien Bustany <adrien@bustany.org>
+
+use std::cell::OnceCell;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use anyhow::{bail, Context, Result};
+use gio::prelude::{FileExt, FileExtManual};
+use serde::{Deserialize, Serialize};
+
+use crate::background::Background;
+use crate::cache::{self, Cache};
+use crate::content::{self, PageObjects};
+use crate::error::{OpenError, PageError, PageStage};
+use crate::format::ThumbnailFormat;
+use crate::geometry::{PageArea, PageBox};
+use crate::info::{non_empty, DocumentInfo, PageInfo};
+use crate::output::{self, OverwritePolicy};
+use crate::pages::PageSelection;
+use crate::parallel;
+use crate::raster::RasterExport;
+use crate::render;
+use crate::template::{self, FilenameTemplate, TemplateValues};
+use crate::thumbnail::{Layout, ThumbnailFit, ThumbnailSize};
+
+/// SVG version the generated files are restricted to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SvgVersion {
+    V1_1,
+    #[default]
+    V1_2,
+}
+
+/// Unit of the width and height of the generated SVG documents. Pages keep
+/// their physical size whatever the unit, unless an SVG width is set.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SvgUnit {
+    /// Points, as in the PDF document.
+    #[default]
+    Pt,
+    /// CSS pixels, at 96 per inch.
+    Px,
+    /// Millimeters.
+    Mm,
+    /// Unitless user units, which browsers treat as CSS pixels.
+    User,
+}
+
+impl SvgUnit {
+    /// Number of units in a point.
+    pub(crate) fn per_point(&self) -> f64 {
+        match self {
+            Self::Pt => 1.,
+            Self::Px | Self::User => 96. / 72.,
+            Self::Mm => 25.4 / 72.,
+        }
+    }
+}
+
+/// Vector formats pages can be exported to, besides SVG.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VectorFormat {
+    /// Single-page PDF document.
+    Pdf,
+    /// PostScript document.
+    Ps,
+    /// Encapsulated PostScript, for embedding in other documents.
+    Eps,
+}
+
+impl VectorFormat {
+    /// Extension of the files, without the leading dot.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Pdf => "pdf",
+            Self::Ps => "ps",
+            Self::Eps => "eps",
+        }
+    }
+
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::Pdf => "PDF",
+            Self::Ps => "PostScript",
+            Self::Eps => "EPS",
+        }
+    }
+}
+
+/// Options controlling how a document gets converted.
+#[derive(Clone, Debug)]
+pub struct ConvertOptions {
+    pub(crate) password: Option<Password>,
+    pub(crate) output_dir: PathBuf,
+    pub(crate) pages: PageSelection,
+    pub(crate) filename_template: FilenameTemplate,
+    pub(crate) page_box: PageBox,
+    pub(crate) write_svg: bool,
+    pub(crate) vector_formats: Vec<VectorFormat>,
+    pub(crate) write_thumbnails: bool,
+    pub(crate) svg_version: SvgVersion,
+    pub(crate) svg_unit: SvgUnit,
+    pub(crate) svg_width: Option<u32>,
+    pub(crate) background: Background,
+    pub(crate) thumbnail_sizes: Vec<ThumbnailSize>,
+    pub(crate) thumbnail_fit: ThumbnailFit,
+    pub(crate) hidpi_thumbnails: bool,
+    pub(crate) thumbnail_supersampling: u32,
+    pub(crate) sharpen_thumbnails: bool,
+    pub(crate) raster: Option<RasterExport>,
+    pub(crate) thumbnail_format: ThumbnailFormat,
+    pub(crate) fallback_resolution: f64,
+    pub(crate) svg_size_limit: Option<u64>,
+    pub(crate) jobs: usize,
+    pub(crate) incremental: bool,
+    pub(crate) overwrite: OverwritePolicy,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        Self {
+            password: None,
+            output_dir: PathBuf::from("."),
+            pages: PageSelection::all(),
+            filename_template: FilenameTemplate::default(),
+            page_box: PageBox::default(),
+            write_svg: true,
+            vector_formats: Vec::new(),
+            write_thumbnails: true,
+            svg_version: SvgVersion::default(),
+            svg_unit: SvgUnit::default(),
+            svg_width: None,
+            background: Background::default(),
+            thumbnail_sizes: vec![ThumbnailSize::from(512)],
+            thumbnail_fit: ThumbnailFit::default(),
+            hidpi_thumbnails: false,
+            thumbnail_supersampling: 1,
+            sharpen_thumbnails: false,
+            raster: None,
+            thumbnail_format: ThumbnailFormat::default(),
+            fallback_resolution: 150.,
+            svg_size_limit: None,
+            jobs: 1,
+            incremental: false,
+            overwrite: OverwritePolicy::default(),
+        }
+    }
+}
+
+impl ConvertOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Password used to open encrypted documents.
+    pub fn password(mut self, password: impl Into<String>) -> Self {
+        self.password = Some(Password(password.into()));
+        self
+    }
+
+    /// Directory where the SVG files and thumbnails get written.
+    pub fn output_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.output_dir = dir.into();
+        self
+    }
+
+    /// Pages to convert (all of them by default).
+    pub fn pages(mut self, pages: PageSelection) -> Self {
+        self.pages = pages;
+        self
+    }
+
+    /// Template for the names of the generated files, which get the `.svg`
+    /// or thumbnail extension appended.
+    pub fn filename_template(mut self, template: FilenameTemplate) -> Self {
+        self.filename_template = template;
+        self
+    }
+
+    /// Page box defining the size of the outputs (the crop box by default).
+    pub fn page_box(mut self, page_box: PageBox) -> Self {
+        self.page_box = page_box;
+        self
+    }
+
+    /// Whether to write an SVG file for each page (enabled by default).
+    pub fn write_svg(mut self, enabled: bool) -> Self {
+        self.write_svg = enabled;
+        self
+    }
+
+    /// Also exports each page to the given vector formats (none by default),
+    /// with the same size as the SVG files, e.g. `001.pdf`.
+    pub fn vector_formats(mut self, formats: impl IntoIterator<Item = VectorFormat>) -> Self {
+        self.vector_formats = formats.into_iter().collect();
+        self.vector_formats.sort_unstable();
+        self.vector_formats.dedup();
+        self
+    }
+
+    /// Whether to write a thumbnail for each page (enabled by default).
+    pub fn write_thumbnails(mut self, enabled: bool) -> Self {
+        self.write_thumbnails = enabled;
+        self
+    }
+
+    pub fn svg_version(mut self, version: SvgVersion) -> Self {
+        self.svg_version = version;
+        self
+    }
+
+    /// Unit of the width and height of the SVG documents (points by
+    /// default).
+    pub fn svg_unit(mut self, unit: SvgUnit) -> Self {
+        self.svg_unit = unit;
+        self
+    }
+
+    /// Scales the pages so that SVG documents are `width` pixels wide,
+    /// instead of keeping their physical size. Requires the `Px` or `User`
+    /// SVG unit.
+    pub fn svg_width(mut self, width: Option<u32>) -> Self {
+        self.svg_width = width;
+        self
+    }
+
+    /// Background painted behind the pages of the SVG files and thumbnails
+    /// (white by default). A transparent background requires a thumbnail
+    /// format supporting transparency, unless thumbnails are disabled.
+    pub fn background(mut self, background: Background) -> Self {
+        self.background = background;
+        self
+    }
+
+    /// Size of the thumbnails, in pixels (512x512 by default). Pages are
+    /// fitted into it according to [`ConvertOptions::thumbnail_fit`], which
+    /// by default scales their longest side to the size.
+    pub fn thumbnail_size(mut self, size: impl Into<ThumbnailSize>) -> Self {
+        self.thumbnail_sizes = vec![size.into()];
+        self
+    }
+
+    /// Generates a thumbnail for each of the given sizes, e.g. to build
+    /// `srcset`s. When more than one thumbnail is generated per page, the
+    /// size is appended to their names, e.g. `001-256.jpg`.
+    pub fn thumbnail_sizes(
+        mut self,
+        sizes: impl IntoIterator<Item = impl Into<ThumbnailSize>>,
+    ) -> Self {
+        self.thumbnail_sizes = sizes.into_iter().map(Into::into).collect();
+        self.thumbnail_sizes.sort_unstable();
+        self.thumbnail_sizes.dedup();
+        self
+    }
+
+    /// How pages are fitted into the thumbnail size when their aspect ratios
+    /// differ.
+    pub fn thumbnail_fit(mut self, fit: ThumbnailFit) -> Self {
+        self.thumbnail_fit = fit;
+        self
+    }
+
+    /// Also generates a thumbnail twice as large for each size, for HiDPI
+    /// screens, e.g. `001-256@2x.jpg` (disabled by default).
+    pub fn hidpi_thumbnails(mut self, enabled: bool) -> Self {
+        self.hidpi_thumbnails = enabled;
+        self
+    }
+
+    /// Renders thumbnails at `factor` times their size, then downsamples
+    /// them with a Lanczos filter in linear light, which reduces aliasing of
+    /// thin lines and small text. 1 (the default) disables supersampling.
+    pub fn thumbnail_supersampling(mut self, factor: u32) -> Self {
+        self.thumbnail_supersampling = factor.max(1);
+        self
+    }
+
+    /// Sharpens thumbnails with an unsharp mask (disabled by default).
+    pub fn sharpen_thumbnails(mut self, enabled: bool) -> Self {
+        self.sharpen_thumbnails = enabled;
+        self
+    }
+
+    /// Also exports each page as a full-size raster image, named after the
+    /// page with a `-full` suffix, e.g. `001-full.png` (disabled by
+    /// default).
+    pub fn raster(mut self, raster: RasterExport) -> Self {
+        self.raster = Some(raster);
+        self
+    }
+
+    /// Image format of the thumbnails (JPEG by default).
+    pub fn thumbnail_format(mut self, format: ThumbnailFormat) -> Self {
+        self.thumbnail_format = format;
+        self
+    }
+
+    /// Resolution (in DPI) used by Cairo when it has to rasterize parts of a
+    /// page (150 DPI by default), such as transparency effects it cannot
+    /// express in the output format.
+    pub fn fallback_resolution(mut self, dpi: f64) -> Self {
+        self.fallback_resolution = dpi;
+        self
+    }
+
+    /// Lowers the fallback resolution of pages whose SVG file would be
+    /// larger than `bytes`, down to 72 DPI, making the fallback resolution a
+    /// maximum. Pages may still exceed the limit, as only their rasterized
+    /// parts shrink.
+    pub fn svg_size_limit(mut self, bytes: Option<u64>) -> Self {
+        self.svg_size_limit = bytes;
+        self
+    }
+
+    /// Number of pages converted in parallel, each by its own worker thread
+    /// (1 by default). 0 uses as many workers as there are CPUs.
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// Returns the size and pixel density of each thumbnail of a page.
+    pub(crate) fn thumbnail_variants(&self) -> Vec<(ThumbnailSize, u32)> {
+        let densities: &[u32] = if self.hidpi_thumbnails { &[1, 2] } else { &[1] };
+
+        self.thumbnail_sizes
+            .iter()
+            .flat_map(|&size| densities.iter().map(move |&density| (size, density)))
+            .collect()
+    }
+
+    /// Checks options that are incompatible with each other.
+    pub(crate) fn validate(&self) -> Result<()> {
+        if !(self.fallback_resolution.is_finite() && self.fallback_resolution > 0.) {
+            bail!(
+                "invalid fallback resolution {} DPI",
+                self.fallback_resolution
+            );
+        }
+
+        if let Some(size) = self
+            .thumbnail_sizes
+            .iter()
+            .find(|size| size.width == 0 || size.height == 0)
+        {
+            bail!("invalid thumbnail size {}x{}", size.width, size.height);
+        }
+
+        if let Some(raster) = &self.raster {
+            raster.resolution.check()?;
+        }
+
+        if self.svg_width == Some(0) {
+            bail!("SVG width must not be zero");
+        }
+
+        if self.svg_width.is_some() && !matches!(self.svg_unit, SvgUnit::Px | SvgUnit::User) {
+            bail!("an SVG width requires pixel or user units");
+        }
+
+        if self.background != Background::Transparent {
+            return Ok(());
+        }
+
+        if self.write_thumbnails && !self.thumbnail_format.supports_transparency() {
+            bail!(
+                "{} thumbnails cannot have a transparent background",
+                self.thumbnail_format.extension()
+            );
+        }
+
+        if let Some(raster) = self.raster.filter(|r| !r.format.supports_transparency()) {
+            bail!(
+                "{} images cannot have a transparent background",
+                raster.format.extension()
+            );
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn job_count(&self) -> usize {
+        match self.jobs {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            jobs => jobs,
+        }
+    }
+
+    /// Whether to skip writing the outputs of pages that did not change since
+    /// a previous conversion to the same output directory (disabled by
+    /// default). The state of previous conversions is kept in a cache file in
+    /// the output directory, saved by [`Converter::save_cache`].
+    ///
+    /// Pages are recognized as unchanged from a hash of the PDF objects they
+    /// are drawn from, without rendering them. Documents whose objects cannot
+    /// be read that way are converted in full.
+    pub fn incremental(mut self, enabled: bool) -> Self {
+        self.incremental = enabled;
+        self
+    }
+
+    /// How files that already exist in the output directory are handled
+    /// (replaced by default).
+    pub fn overwrite(mut self, policy: OverwritePolicy) -> Self {
+        self.overwrite = policy;
+        self
+    }
+
+    /// Opens the PDF file at `path` for conversion with these options.
+    pub fn open(self, path: impl AsRef<Path>) -> Result<Converter> {
+        Converter::open(path, self)
+    }
+
+    /// Opens a PDF document held in memory for conversion with these options.
+    pub fn open_bytes(self, data: impl AsRef<[u8]> + Send + 'static) -> Result<Converter> {
+        Converter::from_bytes(data, self)
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Password(String);
+
+// keep passwords out of logs
+impl std::fmt::Debug for Password {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Password(..)")
+    }
+}
+
+/// A generated thumbnail.
+#[derive(Clone, Debug)]
+pub struct Thumbnail {
+    pub path: PathBuf,
+    /// Requested size, in pixels at a density of 1.
+    pub size: ThumbnailSize,
+    /// Pixel density, 2 for HiDPI thumbnails whose dimensions are twice the
+    /// requested size.
+    pub density: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A full-size raster image of a page.
+#[derive(Clone, Debug)]
+pub struct RasterImage {
+    pub path: PathBuf,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A page exported to a vector format other than SVG.
+#[derive(Clone, Debug)]
+pub struct VectorFile {
+    pub format: VectorFormat,
+    pub path: PathBuf,
+}
+
+/// Information about a converted page.
+#[derive(Clone, Debug)]
+pub struct PageOutput {
+    pub page: PageInfo,
+    pub svg_path: Option<PathBuf>,
+    /// Exports to the other vector formats.
+    pub vector_files: Vec<VectorFile>,
+    /// Thumbnails of the page, by increasing size.
+    pub thumbnails: Vec<Thumbnail>,
+    pub raster: Option<RasterImage>,
+    /// Whether the files were written, as opposed to being left untouched
+    /// because the page did not change since a previous incremental
+    /// conversion.
+    pub regenerated: bool,
+}
+
+/// Where a document gets loaded from. Workers converting pages in parallel
+/// each open their own document from it, as poppler documents cannot be
+/// shared across threads.
+#[derive(Clone)]
+pub(crate) enum Source {
+    File(String),
+    Bytes(gio::glib::Bytes),
+}
+
+impl Source {
+    fn open(&self, password: Option<&Password>) -> Result<poppler::Document> {
+        let password = password.map(|p| p.0.as_str());
+        let res = match self {
+            Source::File(uri) => poppler::Document::from_file(uri, password),
+            Source::Bytes(bytes) => poppler::Document::from_bytes(bytes, password),
+        };
+
+        res.map_err(|err| OpenError::from_glib(err, password.is_some()))
+            .context("error opening PDF document")
+    }
+
+    /// See [`content::read_pages`].
+    fn read_pages(
+        &self,
+        password: Option<&Password>,
+        page_count: i32,
+        hash: bool,
+    ) -> Option<Vec<PageObjects>> {
+        let password = password.map(|p| p.0.as_str());
+
+        match self {
+            Source::File(uri) => {
+                let (data, _) = gio::File::for_uri(uri)
+                    .load_contents(gio::Cancellable::NONE)
+                    .ok()?;
+                content::read_pages(&data, password, page_count, hash)
+            }
+            Source::Bytes(bytes) => content::read_pages(bytes, password, page_count, hash),
+        }
+    }
+}
+
+/// Everything needed to open a [`Converter`] for the same document and
+/// options, which unlike the converter itself can be sent to other threads.
+#[derive(Clone)]
+pub(crate) struct ConverterSeed {
+    source: Source,
+    stem: String,
+    options: ConvertOptions,
+    /// State of incremental conversions, loaded once the output directory
+    /// is locked.
+    cache: Arc<OnceLock<Mutex<Cache>>>,
+    /// Attributes of each page poppler does not expose, if the document
+    /// could be read with lopdf.
+    pages: Option<Arc<[PageObjects]>>,
+    /// Names of the files generated for each page, computed once and shared
+    /// by the workers.
+    names: Arc<OnceLock<Vec<String>>>,
+}
+
+impl ConverterSeed {
+    pub(crate) fn open(self) -> Result<Converter> {
+        let doc = self.source.open(self.options.password.as_ref())?;
+
+        Ok(Converter {
+            doc,
+            seed: self,
+            output_lock: OnceCell::new(),
+        })
+    }
+}
+
+/// An opened PDF document, ready to be converted.
+pub struct Converter {
+    doc: poppler::Document,
+    seed: ConverterSeed,
+    output_lock: OnceCell<File>,
+}
+
+impl Converter {
+    /// Opens the PDF file at `path`. Failures to open the document are
+    /// reported with an [`OpenError`].
+    pub fn open(path: impl AsRef<Path>, options: ConvertOptions) -> Result<Self> {
+        let path = path.as_ref();
+        let source = Source::File(gio::File::for_path(path).uri().into());
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        Self::from_source(source, stem, options)
+    }
+
+    /// Opens a PDF document held in memory, without copying it. The `{stem}`
+    /// placeholder of filename templates expands to "document".
+    pub fn from_bytes(
+        data: impl AsRef<[u8]> + Send + 'static,
+        options: ConvertOptions,
+    ) -> Result<Self> {
+        let source = Source::Bytes(gio::glib::Bytes::from_owned(data));
+        Self::from_source(source, String::from("document"), options)
+    }
+
+    fn from_source(source: Source, stem: String, options: ConvertOptions) -> Result<Self> {
+        let mut converter = ConverterSeed {
+            source,
+            stem,
+            options,
+            cache: Arc::default(),
+            pages: None,
+            names: Arc::default(),
+        }
+        .open()?;
+
+        let options = &converter.seed.options;
+        let pages = converter.seed.source.read_pages(
+            options.password.as_ref(),
+            converter.page_count(),
+            options.incremental,
+        );
+        converter.seed.pages = pages.map(Arc::from);
+
+        Ok(converter)
+    }
+
+    pub(crate) fn seed(&self) -> &ConverterSeed {
+        &self.seed
+    }
+
+    pub fn options(&self) -> &ConvertOptions {
+        &self.seed.options
+    }
+
+    /// Name of the input file without its extension, substituted for the
+    /// `{stem}` placeholder of filename templates.
+    pub fn stem(&self) -> &str {
+        &self.seed.stem
+    }
+
+    /// Name identifying the deck among those converted to the output
+    /// directory: the sanitized stem when the filename template uses
+    /// `{stem}`, so that decks sharing the directory are told apart, and an
+    /// empty string otherwise, as their files then replace each other.
+    pub fn deck(&self) -> String {
+        if self.options().filename_template.uses_stem() {
+            template::sanitize(&self.seed.stem)
+        } else {
+            String::new()
+        }
+    }
+
+    /// Name of the [`Manifest`](crate::Manifest) file in the output
+    /// directory: `manifest.json`, or `STEM.manifest.json` when the filename
+    /// template uses `{stem}`, so that decks sharing the directory each get
+    /// their own (see [`Converter::deck`]).
+    pub fn manifest_name(&self) -> String {
+        match self.deck() {
+            deck if deck.is_empty() => String::from("manifest.json"),
+            deck => format!("{}.manifest.json", deck),
+        }
+    }
+
+    /// Saves the state of incremental conversions to the output directory.
+    /// Does nothing if incremental conversion is disabled, or if the output
+    /// directory was never locked.
+    pub fn save_cache(&self) -> Result<()> {
+        match self.seed.cache.get() {
+            Some(cache) => cache.lock().unwrap().save(),
+            None => Ok(()),
+        }
+    }
+
+    pub fn page_count(&self) -> i32 {
+        self.doc.n_pages()
+    }
+
+    pub fn info(&self) -> DocumentInfo {
+        DocumentInfo::from_document(&self.doc)
+    }
+
+    /// Returns the geometry and metadata of the page at zero-based `index`.
+    pub fn page_info(&self, index: i32) -> Result<PageInfo> {
+        let page = self.page(index)?;
+        let area = self.page_area(&page)?;
+        Ok(page_info(&page, &area, self.page_objects(index)))
+    }
+
+    /// Returns the zero-based indices of the pages selected for conversion.
+    pub fn selected_pages(&self) -> Result<Vec<i32>> {
+        self.options()
+            .pages
+            .resolve(self.page_count())
+            .context("invalid page selection")
+    }
+
+    /// Creates the output directory if needed, and locks it against other
+    /// conversions until the converter is dropped.
+    ///
+    /// The state of incremental conversions gets loaded once the directory is
+    /// locked, so that it includes the pages of conversions that held the
+    /// lock before.
+    pub fn lock_output_dir(&self) -> Result<()> {
+        if self.output_lock.get().is_some() {
+            return Ok(());
+        }
+
+        let lock = output::lock_dir(&self.options().output_dir)?;
+        let _ = self.output_lock.set(lock);
+
+        // without the hashes of their contents, pages cannot be recognized
+        // as unchanged
+        if self.options().incremental && self.seed.pages.is_some() {
+            let cache = Cache::load(&self.options().output_dir);
+            let _ = self.seed.cache.set(Mutex::new(cache));
+        }
+
+        Ok(())
+    }
+
+    /// Converts the selected pages of the document as the returned iterator
+    /// gets consumed, yielding the zero-based index of each page along with
+    /// the result of its conversion. The output directory gets locked with
+    /// [`Converter::lock_output_dir`].
+    ///
+    /// When more than one job is configured, pages are converted by worker
+    /// threads, but results are still yielded in the order of the selection.
+    /// Dropping the iterator stops the workers once they are done with their
+    /// current page.
+    pub fn convert(&self) -> Result<Box<dyn Iterator<Item = (i32, Result<PageOutput>)> + '_>> {
+        let pages = self.selected_pages()?;
+        self.options().validate()?;
+        self.lock_output_dir()?;
+        // computed before starting the workers, so that they share them
+        self.output_names();
+        let jobs = self.options().job_count().min(pages.len());
+
+        if jobs <= 1 {
+            return Ok(Box::new(pages.into_iter().map(|i| (i, self.write_page(i)))));
+        }
+
+        Ok(Box::new(parallel::ParallelPages::start(
+            self.seed(),
+            pages,
+            jobs,
+        )))
+    }
+
+    /// Converts the page at zero-based `index`, writing its SVG file and
+    /// thumbnail to the output directory, which gets locked with
+    /// [`Converter::lock_output_dir`].
+    pub fn convert_page(&self, index: i32) -> Result<PageOutput> {
+        self.options().validate()?;
+        self.lock_output_dir()?;
+        self.write_page(index)
+    }
+
+    /// Same as [`Converter::convert_page`], for callers already holding the
+    /// lock on the output directory.
+    pub(crate) fn write_page(&self, index: i32) -> Result<PageOutput> {
+        let options = self.options();
+        let page = self.page(index)?;
+        let area = self.page_area(&page)?;
+        let info = page_info(&page, &area, self.page_objects(index));
+
+        let out_dir = &options.output_dir;
+        let name = &self.output_names()[index as usize];
+        let hash = self
+            .page_objects(index)
+            .and_then(|page| page.hash.as_deref());
+        let cache_key = match (self.seed.cache.get(), hash) {
+            (Some(cache), Some(hash)) => {
+                let key = cache::page_key(options, &area, hash);
+
+                if let Some(output) = cache.lock().unwrap().lookup(name, &key, &info) {
+                    return Ok(output);
+                }
+
+                Some(key)
+            }
+            _ => None,
+        };
+
+        let recording = render::record_page(&page, &area)
+            .with_context(|| PageError::new(index, PageStage::Render))?;
+
+        // whether some existing files were kept instead of being written
+        let mut skipped = false;
+
+        let svg_path = if options.write_svg {
+            let svg_path = out_dir.join(format!("{}.svg", name));
+            self.write_rendered(&svg_path, &mut skipped, || {
+                render::render_svg(&recording, &area, options)
+            })
+            .with_context(|| PageError::new(index, PageStage::Svg))?;
+            Some(svg_path)
+        } else {
+            None
+        };
+
+        let vector_files = options
+            .vector_formats
+            .iter()
+            .map(|&format| {
+                let path = out_dir.join(format!("{}.{}", name, format.extension()));
+                self.write_rendered(&path, &mut skipped, || {
+                    render::render_vector(&recording, &area, format, options)
+                })
+                .with_context(|| PageError::new(index, PageStage::Vector))?;
+
+                Ok(VectorFile { format, path })
+            })
+            .collect::<Result<_>>()?;
+
+        let variants = if options.write_thumbnails {
+            options.thumbnail_variants()
+        } else {
+            Vec::new()
+        };
+        let extension = options.thumbnail_format.extension();
+        let thumbnails = variants
+            .iter()
+            .map(|&(size, density)| {
+                let path = match (variants.len(), density) {
+                    (1, _) => out_dir.join(format!("{}.{}", name, extension)),
+                    (_, 1) => out_dir.join(format!("{}-{}.{}", name, size, extension)),
+                    _ => out_dir.join(format!("{}-{}@{}x.{}", name, size, density, extension)),
+                };
+                let (width, height) = options
+                    .thumbnail_fit
+                    .layout(area.width, area.height, size.scaled(density))
+                    .and_then(|layout| {
+                        self.write_image(&path, &layout, &mut skipped, |layout| {
+                            render::render_thumbnail(&recording, layout, options)
+                        })
+                    })
+                    .with_context(|| PageError::new(index, PageStage::Thumbnail))?;
+
+                Ok(Thumbnail {
+                    path,
+                    size,
+                    density,
+                    width,
+                    height,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        let raster = match &options.raster {
+            Some(raster) => {
+                let path = out_dir.join(format!("{}-full.{}", name, raster.format.extension()));
+                let (width, height) = raster
+                    .resolution
+                    .layout(area.width, area.height)
+                    .and_then(|layout| {
+                        self.write_image(&path, &layout, &mut skipped, |layout| {
+                            render::render_raster(&recording, layout, &raster.format, options)
+                        })
+                    })
+                    .with_context(|| PageError::new(index, PageStage::Raster))?;
+
+                Some(RasterImage {
+                    path,
+                    width,
+                    height,
+                })
+            }
+            None => None,
+        };
+
+        let output = PageOutput {
+            page: info,
+            svg_path,
+            vector_files,
+            thumbnails,
+            raster,
+            regenerated: !skipped,
+        };
+
+        // kept files were not generated from the current key
+        if let (Some(cache), Some(key), false) = (self.seed.cache.get(), cache_key, skipped) {
+            cache
+                .lock()
+                .unwrap()
+                .insert(name, key, &output)
+                .context("error updating cache")?;
+        }
+
+        Ok(output)
+    }
+
+    /// Renders a file with `render` and writes it to `path`, unless it exists
+    /// and existing files are skipped, in which case it is not rendered
+    /// either. Sets `skipped` if the file was kept.
+    fn write_rendered(
+        &self,
+        path: &Path,
+        skipped: &mut bool,
+        render: impl FnOnce() -> Result<Vec<u8>>,
+    ) -> Result<()> {
+        let overwrite = self.options().overwrite;
+
+        if overwrite == OverwritePolicy::SkipExisting && path.exists() {
+            *skipped = true;
+        } else {
+            let data = render()?;
+            *skipped |= !output::write_output(path, &data, overwrite)?;
+        }
+
+        Ok(())
+    }
+
+    /// Same as [`Converter::write_rendered`], for an image placed according
+    /// to `layout`. Returns its dimensions in pixels.
+    fn write_image(
+        &self,
+        path: &Path,
+        layout: &Layout,
+        skipped: &mut bool,
+        render: impl FnOnce(&Layout) -> Result<Vec<u8>>,
+    ) -> Result<(u32, u32)> {
+        self.write_rendered(path, skipped, || render(layout))?;
+
+        Ok((layout.width, layout.height))
+    }
+
+    /// Returns the names of the files generated for each page, without
+    /// extension. They are computed for the whole document, so that the name
+    /// of a page does not depend on the selected pages.
+    fn output_names(&self) -> &[String] {
+        self.seed.names.get_or_init(|| {
+            let template = &self.options().filename_template;
+            let mut names = (0..self.page_count())
+                .map(|index| {
+                    let page = self.doc.page(index);
+                    let label = page.as_ref().and_then(|page| non_empty(page.label()));
+                    let text = page
+                        .as_ref()
+                        .filter(|_| template.uses_title())
+                        .and_then(|page| page.text());
+                    let title = text
+                        .as_deref()
+                        .and_then(|text| text.lines().map(str::trim).find(|line| !line.is_empty()));
+
+                    template.render(&TemplateValues {
+                        index,
+                        page_count: self.page_count(),
+                        label: label.as_deref(),
+                        stem: &self.seed.stem,
+                        title,
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            template::disambiguate(&mut names);
+            names
+        })
+    }
+
+    fn page_area(&self, page: &poppler::Page) -> Result<PageArea> {
+        let objects = self.page_objects(page.index());
+
+        PageArea::new(page, self.options().page_box, objects)
+            .with_context(|| PageError::new(page.index(), PageStage::Geometry))
+    }
+
+    fn page_objects(&self, index: i32) -> Option<&PageObjects> {
+        self.seed.pages.as_ref()?.get(usize::try_from(index).ok()?)
+    }
+
+    fn page(&self, index: i32) -> Result<poppler::Page> {
+        self.doc
+            .page(index)
+            .with_context(|| PageError::new(index, PageStage::Load))
+    }
+}
+
+fn page_info(page: &poppler::Page, area: &PageArea, objects: Option<&PageObjects>) -> PageInfo {
+    let rotation = match objects {
+        Some(objects) => objects.rotation,
+        None if PageArea::is_quarter_turned(page) => 90,
+        None => 0,
+    };
+
+    PageInfo {
+        index: page.index(),
+        label: non_empty(page.label()),
+        width: area.width,
+        height: area.height,
+        rotation,
+    }
+}
@@ -0,0 +1,104 @@
+// Copyright (C) 2024 Adrien Bustany <adrien@bustany.org>
+
+use std::fmt;
+
+/// Reason why a PDF document could not be opened.
+///
+/// Returned (wrapped in an [`anyhow::Error`]) by the functions opening
+/// documents, so that callers can react to specific failures, for example by
+/// asking for a password.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OpenError {
+    /// The document is encrypted, and no password was given.
+    PasswordRequired,
+    /// The document is encrypted, and the given password is wrong.
+    WrongPassword,
+    /// The file is not a PDF document, or is damaged.
+    Corrupt(String),
+    /// The file could not be read.
+    Io(String),
+}
+
+impl OpenError {
+    pub(crate) fn from_glib(err: gio::glib::Error, has_password: bool) -> Self {
+        match err.kind::<poppler::Error>() {
+            Some(poppler::Error::Encrypted) if has_password => Self::WrongPassword,
+            Some(poppler::Error::Encrypted) => Self::PasswordRequired,
+            Some(
+                poppler::Error::Invalid | poppler::Error::BadCatalog | poppler::Error::Damaged,
+            ) => Self::Corrupt(err.message().to_owned()),
+            _ => Self::Io(err.message().to_owned()),
+        }
+    }
+}
+
+impl fmt::Display for OpenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PasswordRequired => {
+                write!(f, "the document is encrypted, a password is required")
+            }
+            Self::WrongPassword => write!(f, "wrong password"),
+            Self::Corrupt(msg) => write!(f, "invalid or damaged PDF file: {}", msg),
+            Self::Io(msg) => write!(f, "error reading file: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for OpenError {}
+
+/// Step of the conversion of a page that failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PageStage {
+    /// Loading the page from the document.
+    Load,
+    /// Computing the size of the page.
+    Geometry,
+    /// Interpreting the page contents.
+    Render,
+    /// Rendering the SVG file.
+    Svg,
+    /// Rendering the PDF, PostScript or EPS files.
+    Vector,
+    /// Rendering or saving the thumbnail.
+    Thumbnail,
+    /// Rendering or saving the full-size raster image.
+    Raster,
+}
+
+/// Context attached to the errors returned when converting a page, telling
+/// which page and which step failed. It can be retrieved from an
+/// [`anyhow::Error`] with `downcast_ref`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PageError {
+    /// Zero-based index of the page.
+    pub index: i32,
+    pub stage: PageStage,
+}
+
+impl PageError {
+    pub(crate) fn new(index: i32, stage: PageStage) -> Self {
+        Self { index, stage }
+    }
+}
+
+impl fmt::Display for PageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let number = self.index + 1;
+
+        match self.stage {
+            PageStage::Load => write!(f, "error accessing page {}", number),
+            PageStage::Geometry => write!(f, "error computing the size of page {}", number),
+            PageStage::Render => write!(f, "error rendering page {}", number),
+            PageStage::Svg => write!(f, "error rendering SVG file for page {}", number),
+            PageStage::Vector => write!(
+                f,
+                "error rendering PDF, PostScript or EPS file for page {}",
+                number
+            ),
+            PageStage::Thumbnail => write!(f, "error rendering thumbnail for page {}", number),
+            PageStage::Raster => write!(f, "error rendering raster image for page {}", number),
+        }
+    }
+}
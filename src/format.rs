@@ -0,0 +1,108 @@
+// Copyright (C) 2024 Adrien Bustany <adrien@bustany.org>
+
+use anyhow::{bail, Context, Result};
+use image::codecs::avif::AvifEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+use image::codecs::webp::WebPEncoder;
+use image::{ExtendedColorType, ImageEncoder};
+
+/// Image format of the thumbnails, along with its encoding settings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ThumbnailFormat {
+    /// JPEG, with a quality between 1 (worst) and 100 (best).
+    Jpeg { quality: u8 },
+    /// PNG, which is lossless.
+    Png { compression: PngCompression },
+    /// Lossless WebP, the only kind of WebP supported by the encoder.
+    WebP,
+    /// AVIF, with a quality between 1 (worst) and 100 (best), and a speed
+    /// between 1 (slowest, smallest files) and 10 (fastest).
+    Avif { quality: u8, speed: u8 },
+}
+
+impl Default for ThumbnailFormat {
+    fn default() -> Self {
+        Self::Jpeg { quality: 75 }
+    }
+}
+
+impl ThumbnailFormat {
+    /// Extension of the thumbnail files, without the leading dot.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Jpeg { .. } => "jpg",
+            Self::Png { .. } => "png",
+            Self::WebP => "webp",
+            Self::Avif { .. } => "avif",
+        }
+    }
+
+    /// Whether the format can store transparent images.
+    pub fn supports_transparency(&self) -> bool {
+        !matches!(self, Self::Jpeg { .. })
+    }
+
+    /// Encodes an image made of packed 8-bit RGB pixels.
+    pub(crate) fn encode_rgb(&self, data: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
+        self.encode(data, width, height, ExtendedColorType::Rgb8)
+    }
+
+    /// Encodes an image made of packed 8-bit RGBA pixels, with straight
+    /// (not premultiplied) alpha.
+    pub(crate) fn encode_rgba(&self, data: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
+        if !self.supports_transparency() {
+            bail!("{} images cannot be transparent", self.extension());
+        }
+
+        self.encode(data, width, height, ExtendedColorType::Rgba8)
+    }
+
+    fn encode(
+        &self,
+        data: &[u8],
+        width: u32,
+        height: u32,
+        color: ExtendedColorType,
+    ) -> Result<Vec<u8>> {
+        let mut encoded = Vec::new();
+
+        match *self {
+            Self::Jpeg { quality } => JpegEncoder::new_with_quality(&mut encoded, quality.max(1))
+                .write_image(data, width, height, color),
+            Self::Png { compression } => {
+                PngEncoder::new_with_quality(&mut encoded, compression.into(), FilterType::Adaptive)
+                    .write_image(data, width, height, color)
+            }
+            Self::WebP => {
+                WebPEncoder::new_lossless(&mut encoded).write_image(data, width, height, color)
+            }
+            Self::Avif { quality, speed } => {
+                AvifEncoder::new_with_speed_quality(&mut encoded, speed.max(1), quality.max(1))
+                    .write_image(data, width, height, color)
+            }
+        }
+        .with_context(|| format!("error encoding {} image", self.extension()))?;
+
+        Ok(encoded)
+    }
+}
+
+/// Compression level of PNG files, trading file size for encoding speed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PngCompression {
+    Fast,
+    #[default]
+    Default,
+    Best,
+}
+
+impl From<PngCompression> for CompressionType {
+    fn from(v: PngCompression) -> Self {
+        match v {
+            PngCompression::Fast => CompressionType::Fast,
+            PngCompression::Default => CompressionType::Default,
+            PngCompression::Best => CompressionType::Best,
+        }
+    }
+}
@@ -0,0 +1,246 @@
+// Copyright (C) 2024 Adrien Bustany <adrien@bustany.org>
+
+use anyhow::{bail, Context, Result};
+use gio::glib::translate::{ToGlibPtr, ToGlibPtrMut};
+
+use crate::content::PageObjects;
+
+/// Page box defining the area of a page that ends up in the outputs.
+///
+/// poppler always clips rendering to the crop box, so the parts of the other
+/// boxes lying outside of it cannot be rendered: they get clipped to the
+/// crop box.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PageBox {
+    /// The whole page. As it gets clipped to the crop box, this is the crop
+    /// box for pages that define one.
+    Media,
+    /// The visible area of the page, as displayed by PDF viewers.
+    #[default]
+    Crop,
+    /// The intended dimensions of the finished page, after trimming.
+    Trim,
+    /// The bounding box of everything drawn on the page.
+    Content,
+}
+
+impl PageBox {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Media => "media",
+            Self::Crop => "crop",
+            Self::Trim => "trim",
+            Self::Content => "content",
+        }
+    }
+}
+
+/// Rectangle in the default user space of a page: origin at the bottom-left
+/// corner, Y axis pointing up.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Rect {
+    pub x1: f64,
+    pub y1: f64,
+    pub x2: f64,
+    pub y2: f64,
+}
+
+impl Rect {
+    /// Returns the intersection of two rectangles, or `None` if they do not
+    /// overlap.
+    pub(crate) fn intersect(&self, other: &Rect) -> Option<Rect> {
+        let rect = Rect {
+            x1: self.x1.max(other.x1),
+            y1: self.y1.max(other.y1),
+            x2: self.x2.min(other.x2),
+            y2: self.y2.min(other.y2),
+        };
+
+        (rect.x1 < rect.x2 && rect.y1 < rect.y2).then_some(rect)
+    }
+}
+
+/// Rendered area of a page, in points, in the coordinate space poppler
+/// renders to: origin at the top-left corner of the crop box, Y axis pointing
+/// down.
+///
+/// poppler applies the page rotation (/Rotate) when rendering, so the area is
+/// expressed in rotated coordinates: the width and height of a page rotated
+/// by 90 or 270 degrees are those of its boxes, swapped.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct PageArea {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl PageArea {
+    /// Returns the area of `page_box`. The media and trim boxes are read
+    /// from `objects`, and cannot be used without them.
+    pub(crate) fn new(
+        page: &poppler::Page,
+        page_box: PageBox,
+        objects: Option<&PageObjects>,
+    ) -> Result<Self> {
+        let (page_width, page_height) = page.size();
+
+        match page_box {
+            PageBox::Media | PageBox::Trim => {
+                let objects = objects.with_context(|| {
+                    format!("the {} box of the page cannot be read", page_box.name())
+                })?;
+                let rect = match page_box {
+                    PageBox::Media => objects.media_box,
+                    _ => objects.trim_box,
+                };
+                let Some(rect) = rect.intersect(&objects.crop_box) else {
+                    bail!(
+                        "the {} box of the page lies outside of its crop box",
+                        page_box.name()
+                    );
+                };
+
+                Ok(Self::in_crop_box(
+                    &rect,
+                    &objects.crop_box,
+                    objects.rotation,
+                ))
+            }
+            PageBox::Crop => Ok(Self {
+                x: 0.,
+                y: 0.,
+                width: page_width,
+                height: page_height,
+            }),
+            PageBox::Content => {
+                let mut rect = poppler::Rectangle::new();
+
+                if !page.get_bounding_box(&mut rect) {
+                    bail!("error getting bounding box");
+                }
+
+                // the bounding box has its origin at the bottom-left corner
+                // of the page, with the Y axis pointing up
+                Ok(Self {
+                    x: rect.x1(),
+                    y: page_height - rect.y2(),
+                    width: rect.x2() - rect.x1(),
+                    height: rect.y2() - rect.y1(),
+                })
+            }
+        }
+    }
+
+    /// Converts `rect`, lying within `crop_box`, to the rotated coordinate
+    /// space of the rendered page.
+    fn in_crop_box(rect: &Rect, crop_box: &Rect, rotation: u16) -> Self {
+        let (width, height) = (crop_box.x2 - crop_box.x1, crop_box.y2 - crop_box.y1);
+        // corners relative to the top-left corner of the unrotated crop box,
+        // with the Y axis pointing down
+        let corners = [
+            (rect.x1 - crop_box.x1, crop_box.y2 - rect.y2),
+            (rect.x2 - crop_box.x1, crop_box.y2 - rect.y1),
+        ];
+        // pages are rotated clockwise
+        let [(x1, y1), (x2, y2)] = corners.map(|(x, y)| match rotation {
+            90 => (height - y, x),
+            180 => (width - x, height - y),
+            270 => (y, width - x),
+            _ => (x, y),
+        });
+
+        Self {
+            x: x1.min(x2),
+            y: y1.min(y2),
+            width: (x2 - x1).abs(),
+            height: (y2 - y1).abs(),
+        }
+    }
+
+    /// Whether the page is rotated by a quarter turn (90 or 270 degrees), for
+    /// documents whose rotation cannot be read with lopdf.
+    ///
+    /// poppler does not expose the rotation of a page, so it is deduced by
+    /// comparing the rotated page size with the (unrotated) crop box. This
+    /// cannot detect a half turn, nor a quarter turn of a square page, neither
+    /// of which changes the size of the outputs.
+    pub(crate) fn is_quarter_turned(page: &poppler::Page) -> bool {
+        let (width, height) = page.size();
+        let crop_box = crop_box(page);
+        let (crop_width, crop_height) = (
+            (crop_box.x2() - crop_box.x1()).abs(),
+            (crop_box.y2() - crop_box.y1()).abs(),
+        );
+
+        (width - height).abs() > EPSILON
+            && (width - crop_height).abs() < EPSILON
+            && (height - crop_width).abs() < EPSILON
+    }
+
+    /// Sets up `ctx` so that the top-left corner of the area gets drawn at
+    /// the origin.
+    pub(crate) fn apply(&self, ctx: &cairo::Context) {
+        ctx.translate(-self.x, -self.y);
+    }
+}
+
+const EPSILON: f64 = 0.01;
+
+fn crop_box(page: &poppler::Page) -> poppler::Rectangle {
+    let mut rect = poppler::Rectangle::new();
+
+    // not wrapped by poppler-rs
+    unsafe {
+        poppler::ffi::poppler_page_get_crop_box(page.to_glib_none().0, rect.to_glib_none_mut().0);
+    }
+
+    rect
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area(rotation: u16) -> (f64, f64, f64, f64) {
+        // 100x50 crop box at (10, 20), with a 20x10 box 5 points from its
+        // left edge and 10 points from its top edge
+        let crop_box = Rect {
+            x1: 10.,
+            y1: 20.,
+            x2: 110.,
+            y2: 70.,
+        };
+        let rect = Rect {
+            x1: 15.,
+            y1: 50.,
+            x2: 35.,
+            y2: 60.,
+        };
+        let area = PageArea::in_crop_box(&rect, &crop_box, rotation);
+
+        (area.x, area.y, area.width, area.height)
+    }
+
+    #[test]
+    fn in_crop_box() {
+        assert_eq!(area(0), (5., 10., 20., 10.));
+        assert_eq!(area(90), (30., 5., 10., 20.));
+        assert_eq!(area(180), (75., 30., 20., 10.));
+        assert_eq!(area(270), (10., 75., 10., 20.));
+    }
+
+    #[test]
+    fn intersect() {
+        let rect = |x1, y1, x2, y2| Rect { x1, y1, x2, y2 };
+
+        assert_eq!(
+            rect(0., 0., 10., 10.).intersect(&rect(5., -5., 20., 8.)),
+            Some(rect(5., 0., 10., 8.))
+        );
+        assert_eq!(
+            rect(0., 0., 10., 10.).intersect(&rect(10., 0., 20., 10.)),
+            None
+        );
+    }
+}
@@ -0,0 +1,387 @@
+// Copyright (C) 2024 Adrien Bustany <adrien@bustany.org>
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::output::write_json;
+use crate::PageOutput;
+
+const HISTORY_FILENAME: &str = ".pdf2svgslides-outputs.json";
+
+/// Record of the files written to an output directory by successive
+/// conversions, used to find and delete the files that no longer correspond
+/// to a page, for example after pages were removed from the document or the
+/// filename template changed.
+///
+/// Files are recorded separately for each deck converted to the directory
+/// under names of its own (see [`Converter::deck`](crate::Converter::deck)),
+/// so that converting a deck never deletes the files of another one.
+///
+/// The record is kept in a hidden file of the output directory, and must be
+/// saved after each conversion with [`OutputHistory::save`].
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct OutputHistory {
+    #[serde(skip)]
+    output_dir: PathBuf,
+    #[serde(skip)]
+    deck: String,
+    // by deck name
+    #[serde(default)]
+    decks: BTreeMap<String, DeckFiles>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct DeckFiles {
+    // every file written and not deleted since, by name
+    files: BTreeSet<String>,
+    // files currently written for each page, by zero-based index
+    pages: BTreeMap<i32, PageFiles>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct PageFiles {
+    svg: Option<String>,
+    #[serde(default)]
+    vector_files: Vec<String>,
+    #[serde(default)]
+    thumbnails: Vec<String>,
+    #[serde(default)]
+    raster: Option<String>,
+}
+
+impl OutputHistory {
+    /// Creates an empty history for `output_dir`, replacing any existing one
+    /// when saved. `deck` identifies the converted document among those
+    /// sharing the directory (see [`Converter::deck`](crate::Converter::deck)).
+    pub fn new(output_dir: &Path, deck: &str) -> Self {
+        Self {
+            output_dir: output_dir.to_owned(),
+            deck: deck.to_owned(),
+            decks: BTreeMap::new(),
+        }
+    }
+
+    /// Loads the history of `output_dir`, recording the files of `deck` (see
+    /// [`OutputHistory::new`]). A missing history is treated as empty.
+    ///
+    /// So as not to lose the files recorded by concurrent conversions, the
+    /// history should be loaded once the output directory is locked with
+    /// [`Converter::lock_output_dir`](crate::Converter::lock_output_dir).
+    pub fn load(output_dir: &Path, deck: &str) -> Result<Self> {
+        let path = output_dir.join(HISTORY_FILENAME);
+        let mut history = match std::fs::read(&path) {
+            Ok(data) => serde_json::from_slice::<OutputHistory>(&data)
+                .with_context(|| format!("error parsing {}", path.display()))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(err) => {
+                return Err(err).with_context(|| format!("error reading {}", path.display()))
+            }
+        };
+
+        // never touch anything outside of the output directory, whatever the
+        // file says
+        for deck in history.decks.values_mut() {
+            deck.files.retain(|name| is_plain_name(name));
+        }
+        output_dir.clone_into(&mut history.output_dir);
+        deck.clone_into(&mut history.deck);
+
+        Ok(history)
+    }
+
+    /// Records the files written for a converted page. Files of a kind that
+    /// was not generated this time (e.g. SVG files when only generating
+    /// thumbnails) are left as recorded.
+    pub fn record(&mut self, output: &PageOutput) {
+        let deck = self.decks.entry(self.deck.clone()).or_default();
+        let files = deck.pages.entry(output.page.index).or_default();
+
+        if let Some(path) = &output.svg_path {
+            files.svg = file_name(path);
+        }
+
+        if !output.vector_files.is_empty() {
+            files.vector_files = output
+                .vector_files
+                .iter()
+                .filter_map(|file| file_name(&file.path))
+                .collect();
+        }
+
+        if !output.thumbnails.is_empty() {
+            files.thumbnails = output
+                .thumbnails
+                .iter()
+                .filter_map(|thumbnail| file_name(&thumbnail.path))
+                .collect();
+        }
+
+        if let Some(raster) = &output.raster {
+            files.raster = file_name(&raster.path);
+        }
+
+        deck.files.extend(files.names().cloned());
+    }
+
+    /// Returns the files written by previous conversions of the deck which
+    /// do not belong to any of its first `page_count` pages anymore, nor to
+    /// any page of another deck.
+    pub fn stale_files(&self, page_count: i32) -> Vec<PathBuf> {
+        let Some(deck) = self.decks.get(&self.deck) else {
+            return Vec::new();
+        };
+        let current = deck.current_files(page_count);
+        let others: BTreeSet<&String> = self
+            .decks
+            .iter()
+            .filter(|&(name, _)| *name != self.deck)
+            .flat_map(|(_, deck)| deck.pages.values().flat_map(PageFiles::names))
+            .collect();
+
+        deck.files
+            .difference(&current)
+            .filter(|name| !others.contains(name))
+            .map(|name| self.output_dir.join(name))
+            .collect()
+    }
+
+    /// Deletes the files returned by [`OutputHistory::stale_files`], and
+    /// forgets the pages past `page_count`. Returns the deleted files.
+    pub fn remove_stale(&mut self, page_count: i32) -> Result<Vec<PathBuf>> {
+        let mut removed = Vec::new();
+
+        for path in self.stale_files(page_count) {
+            match std::fs::remove_file(&path) {
+                Ok(()) => removed.push(path),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => {
+                    return Err(err).with_context(|| format!("error deleting {}", path.display()));
+                }
+            }
+        }
+
+        if let Some(deck) = self.decks.get_mut(&self.deck) {
+            deck.pages.retain(|&index, _| index < page_count);
+            deck.files = deck.current_files(page_count);
+        }
+
+        Ok(removed)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        write_json(&self.output_dir.join(HISTORY_FILENAME), self)
+            .context("error writing output history")
+    }
+}
+
+impl DeckFiles {
+    fn current_files(&self, page_count: i32) -> BTreeSet<String> {
+        self.pages
+            .range(..page_count)
+            .flat_map(|(_, files)| files.names().cloned())
+            .collect()
+    }
+}
+
+impl PageFiles {
+    fn names(&self) -> impl Iterator<Item = &String> {
+        self.svg
+            .iter()
+            .chain(&self.vector_files)
+            .chain(&self.thumbnails)
+            .chain(&self.raster)
+    }
+}
+
+fn file_name(path: &Path) -> Option<String> {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+}
+
+fn is_plain_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && !name.contains(['/', '\\'])
+        && name != HISTORY_FILENAME
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PageInfo, Thumbnail, ThumbnailSize};
+
+    /// Output directory removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "pdf2svgslides-history-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+
+        fn create(&self, names: &[&str]) {
+            for name in names {
+                std::fs::write(self.0.join(name), name).unwrap();
+            }
+        }
+
+        fn files(&self) -> Vec<String> {
+            let mut names: Vec<_> = std::fs::read_dir(&self.0)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+                .filter(|name| name != HISTORY_FILENAME)
+                .collect();
+            names.sort();
+            names
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Records the SVG file and thumbnail of each page, named after
+    /// `names`, and creates them.
+    fn convert(history: &mut OutputHistory, dir: &TempDir, names: &[&str]) {
+        for (index, name) in names.iter().enumerate() {
+            let svg = format!("{}.svg", name);
+            let thumbnail = format!("{}.jpg", name);
+            dir.create(&[&svg, &thumbnail]);
+
+            history.record(&PageOutput {
+                page: PageInfo {
+                    index: index as i32,
+                    label: None,
+                    width: 100.,
+                    height: 100.,
+                    rotation: 0,
+                },
+                svg_path: Some(dir.0.join(svg)),
+                vector_files: Vec::new(),
+                thumbnails: vec![Thumbnail {
+                    path: dir.0.join(thumbnail),
+                    size: ThumbnailSize::from(512),
+                    density: 1,
+                    width: 512,
+                    height: 512,
+                }],
+                raster: None,
+                regenerated: true,
+            });
+        }
+    }
+
+    fn stale(history: &OutputHistory, page_count: i32) -> Vec<String> {
+        let mut names: Vec<_> = history
+            .stale_files(page_count)
+            .iter()
+            .map(|path| file_name(path).unwrap())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn deck_shrinks() {
+        let dir = TempDir::new("shrinks");
+        let mut history = OutputHistory::new(&dir.0, "");
+        convert(&mut history, &dir, &["001", "002", "003"]);
+        history.save().unwrap();
+
+        let mut history = OutputHistory::load(&dir.0, "").unwrap();
+        convert(&mut history, &dir, &["001", "002"]);
+        assert_eq!(stale(&history, 2), ["003.jpg", "003.svg"]);
+
+        let removed = history.remove_stale(2).unwrap();
+        assert_eq!(removed.len(), 2);
+        assert_eq!(dir.files(), ["001.jpg", "001.svg", "002.jpg", "002.svg"]);
+        assert!(stale(&history, 2).is_empty());
+
+        // the removed page is forgotten
+        history.save().unwrap();
+        let history = OutputHistory::load(&dir.0, "").unwrap();
+        assert!(stale(&history, 3).is_empty());
+    }
+
+    #[test]
+    fn template_renamed() {
+        let dir = TempDir::new("renamed");
+        let mut history = OutputHistory::new(&dir.0, "");
+        convert(&mut history, &dir, &["001", "002"]);
+        history.save().unwrap();
+
+        let mut history = OutputHistory::load(&dir.0, "").unwrap();
+        convert(&mut history, &dir, &["intro", "002"]);
+        assert_eq!(stale(&history, 2), ["001.jpg", "001.svg"]);
+
+        history.remove_stale(2).unwrap();
+        assert_eq!(
+            dir.files(),
+            ["002.jpg", "002.svg", "intro.jpg", "intro.svg"]
+        );
+    }
+
+    #[test]
+    fn decks_share_directory() {
+        let dir = TempDir::new("decks");
+        let mut history = OutputHistory::new(&dir.0, "a");
+        convert(&mut history, &dir, &["a-1", "a-2"]);
+        history.save().unwrap();
+
+        let mut history = OutputHistory::load(&dir.0, "b").unwrap();
+        convert(&mut history, &dir, &["b-1", "b-2"]);
+        assert!(stale(&history, 2).is_empty());
+        history.save().unwrap();
+
+        // deck b shrinks, and its first page now has a name deck a uses
+        let mut history = OutputHistory::load(&dir.0, "b").unwrap();
+        convert(&mut history, &dir, &["a-2"]);
+        assert_eq!(
+            stale(&history, 1),
+            ["b-1.jpg", "b-1.svg", "b-2.jpg", "b-2.svg"]
+        );
+
+        history.remove_stale(1).unwrap();
+        assert_eq!(dir.files(), ["a-1.jpg", "a-1.svg", "a-2.jpg", "a-2.svg"]);
+        history.save().unwrap();
+
+        // and deck a shrinks without deleting the file of deck b
+        let mut history = OutputHistory::load(&dir.0, "a").unwrap();
+        convert(&mut history, &dir, &["a-1"]);
+        assert!(stale(&history, 1).is_empty());
+    }
+
+    #[test]
+    fn hostile_names() {
+        let dir = TempDir::new("hostile");
+        let output_dir = dir.0.join("out");
+        std::fs::create_dir(&output_dir).unwrap();
+        dir.create(&["x"]);
+        std::fs::write(output_dir.join("kept.svg"), "").unwrap();
+        std::fs::write(
+            output_dir.join(HISTORY_FILENAME),
+            r#"{"decks": {"": {"files": ["../x", "a/b", "a\\b", "..", "", "kept.svg",
+                ".pdf2svgslides-outputs.json"], "pages": {}}}}"#,
+        )
+        .unwrap();
+
+        let mut history = OutputHistory::load(&output_dir, "").unwrap();
+        assert_eq!(stale(&history, 0), ["kept.svg"]);
+
+        history.remove_stale(0).unwrap();
+        assert_eq!(dir.files(), ["out", "x"]);
+        assert!(output_dir.join(HISTORY_FILENAME).exists());
+    }
+}
@@ -0,0 +1,51 @@
+// Copyright (C) 2024 Adrien Bustany <adrien@bustany.org>
+
+/// Document-level metadata.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct DocumentInfo {
+    pub page_count: i32,
+    pub title: Option<String>,
+    pub author: Option<String>,
+}
+
+impl DocumentInfo {
+    pub(crate) fn from_document(doc: &poppler::Document) -> Self {
+        Self {
+            page_count: doc.n_pages(),
+            title: non_empty(doc.title()),
+            author: non_empty(doc.author()),
+        }
+    }
+}
+
+/// Geometry and metadata of a single page.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct PageInfo {
+    /// Zero-based index of the page in the document.
+    pub index: i32,
+    /// Page label, as defined by the document (e.g. "iv" or "A-3").
+    pub label: Option<String>,
+    /// Page width, in points.
+    pub width: f64,
+    /// Page height, in points.
+    pub height: f64,
+    /// Clockwise rotation of the page (/Rotate), in degrees: 0, 90, 180 or
+    /// 270. Rotation is applied to the outputs, and the width and height
+    /// above are those of the rotated page.
+    ///
+    /// For documents that can only be read by poppler, the rotation is
+    /// deduced from the page size, and half turns are reported as 0.
+    #[serde(default)]
+    pub rotation: u16,
+}
+
+impl PageInfo {
+    /// One-based page number.
+    pub fn number(&self) -> i32 {
+        self.index + 1
+    }
+}
+
+pub(crate) fn non_empty(s: Option<gio::glib::GString>) -> Option<String> {
+    s.map(|s| s.trim().to_owned()).filter(|s| !s.is_empty())
+}
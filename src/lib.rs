@@ -0,0 +1,58 @@
+// Copyright (C) 2024 Adrien Bustany <adrien@bustany.org>
+
+//! Extract the pages of a PDF document as SVG files, along with a thumbnail
+//! (JPEG, PNG, WebP or AVIF) for each page.
+//!
+//! ```no_run
+//! use pdf2svgslides::ConvertOptions;
+//!
+//! let converter = ConvertOptions::new()
+//!     .output_dir("slides")
+//!     .thumbnail_size(256)
+//!     .open("deck.pdf")?;
+//!
+//! for (_, page) in converter.convert()? {
+//!     let page = page?;
+//!     println!("page {}: {:?}", page.page.number(), page.svg_path);
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+mod background;
+mod cache;
+mod content;
+mod convert;
+mod error;
+mod format;
+mod geometry;
+mod history;
+mod info;
+mod manifest;
+mod output;
+mod pages;
+mod parallel;
+mod pixels;
+mod raster;
+mod render;
+mod report;
+mod resample;
+mod template;
+mod thumbnail;
+
+pub use background::Background;
+pub use convert::{
+    ConvertOptions, Converter, PageOutput, RasterImage, SvgUnit, SvgVersion, Thumbnail, VectorFile,
+    VectorFormat,
+};
+pub use error::{OpenError, PageError, PageStage};
+pub use format::{PngCompression, ThumbnailFormat};
+pub use geometry::PageBox;
+pub use history::OutputHistory;
+pub use info::{DocumentInfo, PageInfo};
+pub use manifest::{Manifest, ManifestFile, ManifestImage, ManifestPage, ManifestThumbnail};
+pub use output::OverwritePolicy;
+pub use pages::PageSelection;
+pub use raster::{RasterExport, RasterResolution};
+pub use report::{PageFailure, Report};
+pub use template::FilenameTemplate;
+pub use thumbnail::{ThumbnailFit, ThumbnailSize};
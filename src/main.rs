@@ -1,147 +1,685 @@
 // Copyright (C) 2024 Adrien Bustany <adrien@bustany.org>
 
-use anyhow::{bail, Context, Result};
-use gio::prelude::FileExt;
+use std::io::{IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
 
-fn main() -> Result<()> {
-    let mut args = std::env::args();
-    let arg0 = args.next().unwrap();
+use anyhow::{Context, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use pdf2svgslides::{
+    Background, ConvertOptions, Converter, FilenameTemplate, Manifest, OpenError, OutputHistory,
+    OverwritePolicy, PageBox, PageSelection, PngCompression, RasterExport, RasterResolution,
+    Report, SvgUnit, SvgVersion, ThumbnailFit, ThumbnailFormat, ThumbnailSize, VectorFormat,
+};
 
-    let Some(input_path) = args.next() else {
-        bail!("Usage: {} file.pdf [output_dir]\n\nExtract pages of a PDF as SVG files, and generates a thumbnail for each.", arg0);
-    };
+/// Exit code used when some pages failed to convert with --keep-going.
+const EXIT_PARTIAL_SUCCESS: u8 = 3;
+
+/// Environment variable holding the password of encrypted documents.
+const PASSWORD_VAR: &str = "PDF2SVGSLIDES_PASSWORD";
+
+/// Extract pages of a PDF as SVG files, and generates a thumbnail for each.
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
 
-    let out_dir_arg = args.next();
-    let out_dir = std::path::Path::new(out_dir_arg.as_deref().unwrap_or("."));
+#[derive(Subcommand)]
+enum Command {
+    /// Write an SVG file and a thumbnail for each page
+    Convert {
+        #[command(flatten)]
+        output: OutputArgs,
+        #[command(flatten)]
+        svg: SvgArgs,
+        #[command(flatten)]
+        thumbnail: ThumbnailArgs,
+        /// Do not generate thumbnails
+        #[arg(long)]
+        no_thumbnails: bool,
+        #[command(flatten)]
+        raster: RasterArgs,
+    },
+    /// Print information about a PDF document
+    Info {
+        /// PDF file to inspect, or - to read it from the standard input
+        input: PathBuf,
+        #[command(flatten)]
+        password: PasswordArgs,
+        #[command(flatten)]
+        page_box: PageBoxArgs,
+    },
+    /// Only write a thumbnail for each page
+    Thumbs {
+        #[command(flatten)]
+        output: OutputArgs,
+        #[command(flatten)]
+        thumbnail: ThumbnailArgs,
+    },
+}
+
+#[derive(Args)]
+struct OutputArgs {
+    /// PDF file to convert, or - to read it from the standard input
+    input: PathBuf,
+    #[command(flatten)]
+    password: PasswordArgs,
+    /// Directory where the files get written
+    #[arg(default_value = ".")]
+    output_dir: PathBuf,
+    /// Pages to convert, e.g. "1,3-5,10-" (negative numbers count from the
+    /// last page)
+    #[arg(short, long, value_name = "PAGES", allow_hyphen_values = true)]
+    pages: Option<PageSelection>,
+    /// Name of the generated files, without extension. Supported
+    /// placeholders: {page}, {label}, {stem} (input file name) and {title}
+    /// (first line of text of the page)
+    #[arg(long, value_name = "TEMPLATE", default_value = "{page}")]
+    name: FilenameTemplate,
+    #[command(flatten)]
+    page_box: PageBoxArgs,
+    /// Background of the pages: a color such as "white" or "#336699", or
+    /// "transparent" (thumbnails then have to be PNG, WebP or AVIF)
+    #[arg(long, value_name = "COLOR", default_value = "white")]
+    background: Background,
+    /// Resolution (in DPI) of the parts of a page that have to be rasterized
+    /// (the maximum resolution with --max-svg-size)
+    #[arg(long, value_name = "DPI", default_value_t = 150.)]
+    fallback_resolution: f64,
+    /// Lower the fallback resolution of pages whose SVG file would be larger
+    /// than SIZE bytes, with an optional K or M suffix (such as 500K)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    max_svg_size: Option<u64>,
+    /// Keep converting the other pages when a page fails, and write a report
+    /// listing the failures. Exits with code 3 if some pages failed.
+    #[arg(short, long)]
+    keep_going: bool,
+    /// Path of the JSON report listing the converted and failed pages
+    /// [default with --keep-going: OUTPUT_DIR/report.json]
+    #[arg(long, value_name = "FILE")]
+    report: Option<PathBuf>,
+    /// Do not write OUTPUT_DIR/manifest.json (STEM.manifest.json if --name
+    /// uses {stem}), which describes the generated files of every page
+    /// converted so far
+    #[arg(long)]
+    no_manifest: bool,
+    /// Number of pages converted in parallel (0 for one per CPU)
+    #[arg(short, long, value_name = "N", default_value_t = 1)]
+    jobs: usize,
+    /// Only write the files of the pages that changed since the previous
+    /// conversion to the same output directory
+    #[arg(short, long)]
+    incremental: bool,
+    /// Delete the files written to OUTPUT_DIR by previous conversions that no
+    /// longer correspond to a page, e.g. after pages were removed from the
+    /// document
+    #[arg(long)]
+    prune: bool,
+    /// What to do with files that already exist in OUTPUT_DIR
+    #[arg(long, value_enum, default_value_t = OverwriteArg::Always)]
+    overwrite: OverwriteArg,
+}
 
-    let input_file = gio::File::for_commandline_arg(input_path);
-    let doc =
-        poppler::Document::from_file(&input_file.uri(), None).context("error opening PDF file")?;
+impl OutputArgs {
+    fn options(&self) -> ConvertOptions {
+        ConvertOptions::new()
+            .output_dir(&self.output_dir)
+            .pages(self.pages.clone().unwrap_or_default())
+            .filename_template(self.name.clone())
+            .page_box(self.page_box.page_box.into())
+            .background(self.background)
+            .fallback_resolution(self.fallback_resolution)
+            .svg_size_limit(self.max_svg_size)
+            .jobs(self.jobs)
+            .incremental(self.incremental)
+            .overwrite(self.overwrite.into())
+    }
+}
 
-    let page_count = doc.n_pages();
+#[derive(Clone, Copy, ValueEnum)]
+enum OverwriteArg {
+    /// Replace existing files
+    Always,
+    /// Keep existing files
+    Skip,
+    /// Fail the conversion of pages whose files exist
+    Fail,
+}
 
-    if page_count == 0 {
-        return Ok(());
+impl From<OverwriteArg> for OverwritePolicy {
+    fn from(v: OverwriteArg) -> Self {
+        match v {
+            OverwriteArg::Always => OverwritePolicy::Overwrite,
+            OverwriteArg::Skip => OverwritePolicy::SkipExisting,
+            OverwriteArg::Fail => OverwritePolicy::Fail,
+        }
     }
+}
 
-    for i in 0..page_count {
-        let page_number = 1 + i;
-        let page = doc
-            .page(i)
-            .with_context(|| format!("error accessing page {}", page_number))?;
-        let mut page_rect = poppler::Rectangle::new();
+#[derive(Args)]
+struct PasswordArgs {
+    /// Password of encrypted documents. Without --password or
+    /// --password-file, it is read from the PDF2SVGSLIDES_PASSWORD
+    /// environment variable, or asked for interactively if the document is
+    /// encrypted.
+    #[arg(long)]
+    password: Option<String>,
+    /// Read the password of encrypted documents from the first line of a
+    /// file
+    #[arg(long, value_name = "FILE")]
+    password_file: Option<PathBuf>,
+}
 
-        if !page.get_bounding_box(&mut page_rect) {
-            bail!("error getting bounding box for page {}", page_number);
+impl PasswordArgs {
+    /// Returns the password given by --password, --password-file or the
+    /// environment, in that order of precedence.
+    fn password(&self) -> Result<Option<String>> {
+        if let Some(password) = &self.password {
+            return Ok(Some(password.clone()));
         }
 
-        let width = page_rect.x2() - page_rect.x1();
-        let height = page_rect.y2() - page_rect.y1();
+        let Some(path) = &self.password_file else {
+            return match std::env::var(PASSWORD_VAR) {
+                Ok(password) => Ok(Some(password)),
+                Err(std::env::VarError::NotPresent) => Ok(None),
+                Err(err) => Err(err).with_context(|| format!("invalid {}", PASSWORD_VAR)),
+            };
+        };
+
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("error reading password file {}", path.display()))?;
+        let line = contents.lines().next().unwrap_or_default();
 
-        render_page(&page, page_number, width, height, out_dir)
-            .with_context(|| format!("error rendering page {}", page_number))?;
-        render_thumbnail(&page, page_number, width, height, out_dir)
-            .with_context(|| format!("error rendering thumbnail for page {}", page_number))?;
+        Ok(Some(line.to_owned()))
     }
+}
 
-    Ok(())
+#[derive(Args)]
+struct PageBoxArgs {
+    /// Page box defining the size of the slides
+    #[arg(long, value_enum, default_value_t = PageBoxArg::Crop)]
+    page_box: PageBoxArg,
 }
 
-fn render_page(
-    page: &poppler::Page,
-    page_number: i32,
-    width: f64,
-    height: f64,
-    out_dir: &std::path::Path,
-) -> Result<()> {
-    let svg_filename = out_dir.join(format!("{:03}.svg", page_number));
+#[derive(Clone, Copy, ValueEnum)]
+enum PageBoxArg {
+    /// Whole page, clipped to the crop box as only the crop box can be
+    /// rendered
+    Media,
+    /// Visible area of the page, as displayed by PDF viewers
+    Crop,
+    /// Dimensions of the finished page after trimming, clipped to the crop
+    /// box
+    Trim,
+    /// Bounding box of everything drawn on the page
+    Content,
+}
 
-    let surface = cairo::SvgSurface::new(width, height, Some(&svg_filename))
-        .context("error creating SVG surface")?;
-    surface.restrict(cairo::SvgVersion::_1_2);
-    surface.set_fallback_resolution(150., 150.);
-    let ctx = cairo::Context::new(&surface).context("error creating Cairo context")?;
-    page.render_for_printing(&ctx);
-    ctx.status().context("error rendering page")?;
+impl From<PageBoxArg> for PageBox {
+    fn from(v: PageBoxArg) -> Self {
+        match v {
+            PageBoxArg::Media => PageBox::Media,
+            PageBoxArg::Crop => PageBox::Crop,
+            PageBoxArg::Trim => PageBox::Trim,
+            PageBoxArg::Content => PageBox::Content,
+        }
+    }
+}
 
-    Ok(())
+#[derive(Args)]
+struct SvgArgs {
+    /// SVG version the files are restricted to
+    #[arg(long, value_enum, default_value_t = SvgVersionArg::V1_2)]
+    svg_version: SvgVersionArg,
+    /// Unit of the width and height of the SVG files [default: pt, or px
+    /// with --svg-width]
+    #[arg(long, value_enum)]
+    svg_unit: Option<SvgUnitArg>,
+    /// Scale pages so that SVG files are this many pixels wide
+    #[arg(long, value_name = "PIXELS", value_parser = clap::value_parser!(u32).range(1..))]
+    svg_width: Option<u32>,
+    /// Also export each page in these vector formats (OUTPUT_DIR/001.pdf,
+    /// ...)
+    #[arg(long, value_enum, value_delimiter = ',', value_name = "FORMATS")]
+    export: Vec<VectorFormatArg>,
 }
 
-fn render_thumbnail(
-    page: &poppler::Page,
-    page_number: i32,
-    width: f64,
-    height: f64,
-    out_dir: &std::path::Path,
-) -> Result<()> {
-    let (width, height) = (
-        check_dimension(width).context("invalid width")?,
-        check_dimension(height).context("invalid height")?,
-    );
-    let ratio = scale_ratio(width, height, 512);
-    let (thumb_width, thumb_height) = scale_rect(width, height, ratio);
-    let surface = cairo::ImageSurface::create(
-        cairo::Format::Rgb24,
-        i32::try_from(thumb_width).context("width too big")?,
-        i32::try_from(thumb_height).context("height too big")?,
-    )
-    .context("error creating surface")?;
-    surface.set_fallback_resolution(150., 150.);
-
-    {
-        let ctx = cairo::Context::new(&surface).context("error creating Cairo context")?;
-        ctx.scale(ratio, ratio);
-        page.render_for_printing(&ctx);
-        ctx.status().context("error rendering page thumbnail")?;
-    } // drop context here so that we can access the surface afterwards
-
-    // write the thumbnail to jpeg somehow (using the image crate)
-
-    let buffer = {
-        let thumbnail_data: &[u8] = &surface.take_data().context("error accessing image data")?;
-        let mut rgb_data: Vec<u8> = vec![0; thumbnail_data.len() - thumbnail_data.len() / 4];
-
-        let mut j: usize = 0;
-
-        for i in (0..thumbnail_data.len()).step_by(4) {
-            rgb_data[j] = thumbnail_data[i + 2];
-            rgb_data[j + 1] = thumbnail_data[i + 1];
-            rgb_data[j + 2] = thumbnail_data[i];
-            j += 3;
+#[derive(Clone, Copy, ValueEnum)]
+enum SvgVersionArg {
+    #[value(name = "1.1")]
+    V1_1,
+    #[value(name = "1.2")]
+    V1_2,
+}
+
+impl From<SvgVersionArg> for SvgVersion {
+    fn from(v: SvgVersionArg) -> Self {
+        match v {
+            SvgVersionArg::V1_1 => SvgVersion::V1_1,
+            SvgVersionArg::V1_2 => SvgVersion::V1_2,
         }
+    }
+}
 
-        image::ImageBuffer::<image::Rgb<u8>, _>::from_vec(thumb_width, thumb_height, rgb_data)
-            .unwrap()
-    };
+impl SvgArgs {
+    fn apply(&self, options: ConvertOptions) -> ConvertOptions {
+        let unit = match (self.svg_unit, self.svg_width) {
+            (Some(unit), _) => unit.into(),
+            (None, Some(_)) => SvgUnit::Px,
+            (None, None) => SvgUnit::Pt,
+        };
 
-    let thumbnail_filename = out_dir.join(format!("{:03}.jpg", page_number));
-    buffer
-        .save_with_format(&thumbnail_filename, image::ImageFormat::Jpeg)
-        .context("error saving thumbnail")?;
+        options
+            .svg_version(self.svg_version.into())
+            .svg_unit(unit)
+            .svg_width(self.svg_width)
+            .vector_formats(self.export.iter().map(|&format| format.into()))
+    }
+}
 
-    Ok(())
+#[derive(Clone, Copy, ValueEnum)]
+enum SvgUnitArg {
+    /// Points
+    Pt,
+    /// CSS pixels
+    Px,
+    /// Millimeters
+    Mm,
+    /// Unitless user units
+    User,
+}
+
+impl From<SvgUnitArg> for SvgUnit {
+    fn from(v: SvgUnitArg) -> Self {
+        match v {
+            SvgUnitArg::Pt => SvgUnit::Pt,
+            SvgUnitArg::Px => SvgUnit::Px,
+            SvgUnitArg::Mm => SvgUnit::Mm,
+            SvgUnitArg::User => SvgUnit::User,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum VectorFormatArg {
+    Pdf,
+    /// PostScript
+    Ps,
+    /// Encapsulated PostScript
+    Eps,
 }
 
-fn scale_ratio(w: u32, h: u32, max_size: u32) -> f64 {
-    let side = std::cmp::max(w, h);
-    if side == 0 {
-        return 0.;
+impl From<VectorFormatArg> for VectorFormat {
+    fn from(v: VectorFormatArg) -> Self {
+        match v {
+            VectorFormatArg::Pdf => VectorFormat::Pdf,
+            VectorFormatArg::Ps => VectorFormat::Ps,
+            VectorFormatArg::Eps => VectorFormat::Eps,
+        }
     }
-    f64::from(max_size) / f64::from(side)
 }
 
-fn scale_rect(w: u32, h: u32, ratio: f64) -> (u32, u32) {
-    ((f64::from(w) * ratio) as u32, (f64::from(h) * ratio) as u32)
+#[derive(Args)]
+struct ThumbnailArgs {
+    /// Size of the thumbnails in pixels, either WIDTHxHEIGHT or a single
+    /// number for square sizes. Several sizes can be given, separated by
+    /// commas or by repeating the option, to generate one thumbnail per size.
+    #[arg(
+        long,
+        value_name = "SIZE",
+        value_delimiter = ',',
+        default_value = "512"
+    )]
+    thumbnail_size: Vec<ThumbnailSize>,
+    /// How pages are fitted into the thumbnail size
+    #[arg(long, value_enum, default_value_t = ThumbnailFitArg::Inside)]
+    thumbnail_fit: ThumbnailFitArg,
+    /// Also generate thumbnails twice as large, for HiDPI screens
+    #[arg(long)]
+    hidpi: bool,
+    /// Render thumbnails at N times their size and downsample them, for
+    /// smoother lines and text
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u32).range(1..=8)
+    )]
+    supersample: u32,
+    /// Sharpen thumbnails
+    #[arg(long)]
+    sharpen: bool,
+    /// Image format of the thumbnails
+    #[arg(long, value_enum, default_value_t = ThumbnailFormatArg::Jpeg)]
+    thumbnail_format: ThumbnailFormatArg,
+    /// Quality of JPEG and AVIF thumbnails, from 1 (worst) to 100 (best)
+    /// [default: 75 for JPEG, 80 for AVIF]
+    #[arg(long, value_name = "QUALITY", value_parser = clap::value_parser!(u8).range(1..=100))]
+    thumbnail_quality: Option<u8>,
+    /// Compression level of PNG thumbnails
+    #[arg(long, value_enum, default_value_t = PngCompressionArg::Default)]
+    png_compression: PngCompressionArg,
+    /// Encoding speed of AVIF thumbnails, from 1 (slowest, smallest files)
+    /// to 10 (fastest)
+    #[arg(long, value_name = "SPEED", default_value_t = 4, value_parser = clap::value_parser!(u8).range(1..=10))]
+    avif_speed: u8,
 }
 
-fn check_dimension(dim: f64) -> Result<u32> {
-    if dim < 0. {
-        bail!("value is negative");
+impl ThumbnailArgs {
+    fn apply(&self, options: ConvertOptions) -> ConvertOptions {
+        let format = self.image_format(self.thumbnail_format, self.thumbnail_quality);
+
+        options
+            .thumbnail_sizes(self.thumbnail_size.iter().copied())
+            .thumbnail_fit(self.thumbnail_fit.into())
+            .hidpi_thumbnails(self.hidpi)
+            .thumbnail_supersampling(self.supersample)
+            .sharpen_thumbnails(self.sharpen)
+            .thumbnail_format(format)
     }
 
-    if dim > f64::from(u32::MAX) {
-        bail!("value is too large");
+    /// Returns the settings of an image format, shared by thumbnails and
+    /// raster images except for the quality.
+    fn image_format(&self, format: ThumbnailFormatArg, quality: Option<u8>) -> ThumbnailFormat {
+        match format {
+            ThumbnailFormatArg::Jpeg => ThumbnailFormat::Jpeg {
+                quality: quality.unwrap_or(75),
+            },
+            ThumbnailFormatArg::Png => ThumbnailFormat::Png {
+                compression: self.png_compression.into(),
+            },
+            ThumbnailFormatArg::Webp => ThumbnailFormat::WebP,
+            ThumbnailFormatArg::Avif => ThumbnailFormat::Avif {
+                quality: quality.unwrap_or(80),
+                speed: self.avif_speed,
+            },
+        }
     }
+}
+
+#[derive(Args)]
+struct RasterArgs {
+    /// Also export each page as a full-size image (OUTPUT_DIR/001-full.png,
+    /// ...) at this resolution
+    #[arg(long, value_name = "DPI", conflicts_with = "raster_width")]
+    raster_dpi: Option<f64>,
+    /// Also export each page as a full-size image of this width
+    #[arg(long, value_name = "PIXELS", value_parser = clap::value_parser!(u32).range(1..))]
+    raster_width: Option<u32>,
+    /// Image format of the full-size images
+    #[arg(long, value_enum, default_value_t = ThumbnailFormatArg::Png)]
+    raster_format: ThumbnailFormatArg,
+    /// Quality of JPEG and AVIF full-size images, from 1 (worst) to 100
+    /// (best) [default: 75 for JPEG, 80 for AVIF]
+    #[arg(long, value_name = "QUALITY", value_parser = clap::value_parser!(u8).range(1..=100))]
+    raster_quality: Option<u8>,
+}
+
+impl RasterArgs {
+    fn apply(&self, options: ConvertOptions, thumbnail: &ThumbnailArgs) -> ConvertOptions {
+        let resolution = match (self.raster_dpi, self.raster_width) {
+            (Some(dpi), _) => RasterResolution::Dpi(dpi),
+            (None, Some(width)) => RasterResolution::Width(width),
+            (None, None) => return options,
+        };
+
+        options.raster(RasterExport {
+            resolution,
+            format: thumbnail.image_format(self.raster_format, self.raster_quality),
+        })
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ThumbnailFormatArg {
+    Jpeg,
+    /// Lossless
+    Png,
+    /// Lossless
+    Webp,
+    Avif,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ThumbnailFitArg {
+    /// Scale pages to fit within the size
+    Inside,
+    /// Scale pages to fit within the size, and pad them to the exact size
+    Contain,
+    /// Scale pages to cover the size, and crop them to the exact size
+    Cover,
+    /// Scale pages to the width of the size
+    Width,
+    /// Scale pages to the height of the size
+    Height,
+}
+
+impl From<ThumbnailFitArg> for ThumbnailFit {
+    fn from(v: ThumbnailFitArg) -> Self {
+        match v {
+            ThumbnailFitArg::Inside => ThumbnailFit::Inside,
+            ThumbnailFitArg::Contain => ThumbnailFit::Contain,
+            ThumbnailFitArg::Cover => ThumbnailFit::Cover,
+            ThumbnailFitArg::Width => ThumbnailFit::Width,
+            ThumbnailFitArg::Height => ThumbnailFit::Height,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum PngCompressionArg {
+    Fast,
+    Default,
+    Best,
+}
 
-    Ok(dim as u32)
+impl From<PngCompressionArg> for PngCompression {
+    fn from(v: PngCompressionArg) -> Self {
+        match v {
+            PngCompressionArg::Fast => PngCompression::Fast,
+            PngCompressionArg::Default => PngCompression::Default,
+            PngCompressionArg::Best => PngCompression::Best,
+        }
+    }
+}
+
+/// Parses a size in bytes, with an optional K (KiB) or M (MiB) suffix.
+fn parse_size(s: &str) -> Result<u64, String> {
+    let (digits, multiplier) = match s.strip_suffix(['K', 'k']) {
+        Some(digits) => (digits, 1 << 10),
+        None => match s.strip_suffix(['M', 'm']) {
+            Some(digits) => (digits, 1 << 20),
+            None => (s, 1),
+        },
+    };
+
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|v| v.checked_mul(multiplier))
+        .ok_or_else(|| format!("invalid size \"{}\"", s))
+}
+
+fn main() -> Result<ExitCode> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Command::Convert {
+            output,
+            svg,
+            thumbnail,
+            no_thumbnails,
+            raster,
+        } => {
+            let options = thumbnail.apply(output.options());
+            let options = svg
+                .apply(raster.apply(options, &thumbnail))
+                .write_thumbnails(!no_thumbnails);
+            convert(&output, options)
+        }
+        Command::Info {
+            input,
+            password,
+            page_box,
+        } => info(&input, &password, page_box).map(|_| ExitCode::SUCCESS),
+        Command::Thumbs { output, thumbnail } => {
+            let options = thumbnail.apply(output.options()).write_svg(false);
+            convert(&output, options)
+        }
+    }
+}
+
+fn open(input: &Path, password: &PasswordArgs, options: ConvertOptions) -> Result<Converter> {
+    let data: Option<Arc<[u8]>> = if input == Path::new("-") {
+        let mut data = Vec::new();
+        std::io::stdin()
+            .read_to_end(&mut data)
+            .context("error reading PDF document from the standard input")?;
+        Some(data.into())
+    } else {
+        None
+    };
+
+    let open = |options: ConvertOptions| match &data {
+        Some(data) => options.open_bytes(data.clone()),
+        None => options.open(input),
+    };
+
+    let Some(password) = password.password()? else {
+        return match open(options.clone()) {
+            Err(err)
+                if err.downcast_ref() == Some(&OpenError::PasswordRequired)
+                    && std::io::stdin().is_terminal() =>
+            {
+                let password =
+                    rpassword::prompt_password(format!("Password for {}: ", input.display()))
+                        .context("error reading password")?;
+                open(options.password(password))
+            }
+            res => res,
+        };
+    };
+
+    open(options.password(password))
+}
+
+fn convert(output: &OutputArgs, options: ConvertOptions) -> Result<ExitCode> {
+    let converter = open(&output.input, &output.password, options)?;
+    let report_path = match (&output.report, output.keep_going) {
+        (Some(path), _) => Some(path.clone()),
+        (None, true) => Some(output.output_dir.join("report.json")),
+        (None, false) => None,
+    };
+    let mut report = Report::default();
+    let mut manifest = Manifest::new(converter.info());
+    // the history is read under the lock, so that it includes the files of
+    // conversions that held it before
+    converter.lock_output_dir()?;
+    let deck = converter.deck();
+    let mut history = match OutputHistory::load(&output.output_dir, &deck) {
+        Ok(history) => history,
+        // the history only matters for pruning, it gets rebuilt otherwise
+        Err(err) if !output.prune => {
+            eprintln!("Warning: {:#}, starting a new output history", err);
+            OutputHistory::new(&output.output_dir, &deck)
+        }
+        Err(err) => return Err(err),
+    };
+
+    for (index, result) in converter.convert()? {
+        report.record(index, &result);
+
+        match result {
+            Ok(page) => {
+                history.record(&page);
+                manifest.add_page(&page, &output.output_dir)?;
+            }
+            Err(err) if output.keep_going => eprintln!("Error: {:#}", err),
+            Err(err) => {
+                converter.save_cache()?;
+                history.save()?;
+
+                if let Some(path) = &report_path {
+                    report.write_json(path)?;
+                }
+
+                return Err(err);
+            }
+        }
+    }
+
+    converter.save_cache()?;
+
+    if output.prune {
+        for path in history.remove_stale(converter.page_count())? {
+            eprintln!("Removed {}", path.display());
+        }
+    }
+
+    history.save()?;
+
+    if output.incremental {
+        let pages: Vec<_> = report.regenerated.iter().map(i32::to_string).collect();
+        eprintln!(
+            "Regenerated {} of {} pages: {}",
+            pages.len(),
+            report.converted.len(),
+            pages.join(", ")
+        );
+    }
+
+    if let Some(path) = &report_path {
+        report.write_json(path)?;
+    }
+
+    if !output.no_manifest {
+        let path = output.output_dir.join(converter.manifest_name());
+
+        // keep the pages converted by previous runs
+        match Manifest::load(&path) {
+            Ok(Some(previous)) => manifest.merge(previous),
+            Ok(None) => {}
+            Err(err) => eprintln!("Warning: {:#}, writing a new manifest", err),
+        }
+
+        manifest.write_json(&path)?;
+    }
+
+    if report.is_success() {
+        Ok(ExitCode::SUCCESS)
+    } else if report.converted.is_empty() {
+        Ok(ExitCode::FAILURE)
+    } else {
+        Ok(ExitCode::from(EXIT_PARTIAL_SUCCESS))
+    }
+}
+
+fn info(input: &Path, password: &PasswordArgs, page_box: PageBoxArgs) -> Result<()> {
+    let options = ConvertOptions::new().page_box(page_box.page_box.into());
+    let converter = open(input, password, options)?;
+    let info = converter.info();
+
+    println!("Title:  {}", info.title.as_deref().unwrap_or("-"));
+    println!("Author: {}", info.author.as_deref().unwrap_or("-"));
+    println!("Pages:  {}", info.page_count);
+
+    for i in 0..info.page_count {
+        let page = converter.page_info(i)?;
+        println!(
+            "{:>4} {:>8}  {:.2} x {:.2} pt{}",
+            page.number(),
+            page.label.as_deref().unwrap_or("-"),
+            page.width,
+            page.height,
+            match page.rotation {
+                0 => String::new(),
+                rotation => format!("  (rotated {}°)", rotation),
+            }
+        );
+    }
+
+    Ok(())
 }
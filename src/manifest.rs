@@ -0,0 +1,181 @@
+// Copyright (C) 2024 Adrien Bustany <adrien@bustany.org>
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::output::{to_hex, write_json};
+use crate::{DocumentInfo, PageInfo, PageOutput, ThumbnailSize};
+
+/// Description of a converted deck, listing the files generated for each
+/// page, serializable to JSON.
+///
+/// When only some pages get converted, the manifest can be merged with the
+/// one written by a previous conversion with [`Manifest::merge`], so that it
+/// still describes the whole deck.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    #[serde(flatten)]
+    pub document: DocumentInfo,
+    pub pages: Vec<ManifestPage>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ManifestPage {
+    #[serde(flatten)]
+    pub info: PageInfo,
+    pub svg: Option<ManifestFile>,
+    /// PDF, PostScript and EPS files.
+    pub vector_files: Vec<ManifestFile>,
+    pub thumbnails: Vec<ManifestThumbnail>,
+    pub raster: Option<ManifestImage>,
+}
+
+/// A generated file.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ManifestFile {
+    /// Path of the file, relative to the output directory.
+    pub path: String,
+    /// Hex-encoded SHA-256 hash of the file contents.
+    pub sha256: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ManifestThumbnail {
+    #[serde(flatten)]
+    pub file: ManifestFile,
+    /// Requested size, in pixels at a density of 1.
+    pub size: ThumbnailSize,
+    /// Pixel density, 2 for HiDPI thumbnails.
+    pub density: u32,
+    /// Width in pixels.
+    pub width: u32,
+    /// Height in pixels.
+    pub height: u32,
+}
+
+/// A full-size raster image.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ManifestImage {
+    #[serde(flatten)]
+    pub file: ManifestFile,
+    /// Width in pixels.
+    pub width: u32,
+    /// Height in pixels.
+    pub height: u32,
+}
+
+impl Manifest {
+    pub fn new(document: DocumentInfo) -> Self {
+        Self {
+            document,
+            pages: Vec::new(),
+        }
+    }
+
+    /// Adds a converted page, hashing its files. `output_dir` is the
+    /// directory the page was converted to.
+    pub fn add_page(&mut self, output: &PageOutput, output_dir: &Path) -> Result<()> {
+        let svg = output
+            .svg_path
+            .as_deref()
+            .map(|path| ManifestFile::new(path, output_dir))
+            .transpose()?;
+        let vector_files = output
+            .vector_files
+            .iter()
+            .map(|file| ManifestFile::new(&file.path, output_dir))
+            .collect::<Result<_>>()?;
+        let thumbnails = output
+            .thumbnails
+            .iter()
+            .map(|thumbnail| -> Result<_> {
+                Ok(ManifestThumbnail {
+                    file: ManifestFile::new(&thumbnail.path, output_dir)?,
+                    size: thumbnail.size,
+                    density: thumbnail.density,
+                    width: thumbnail.width,
+                    height: thumbnail.height,
+                })
+            })
+            .collect::<Result<_>>()?;
+        let raster = output
+            .raster
+            .as_ref()
+            .map(|raster| -> Result<_> {
+                Ok(ManifestImage {
+                    file: ManifestFile::new(&raster.path, output_dir)?,
+                    width: raster.width,
+                    height: raster.height,
+                })
+            })
+            .transpose()?;
+
+        self.pages.push(ManifestPage {
+            info: output.page.clone(),
+            svg,
+            vector_files,
+            thumbnails,
+            raster,
+        });
+        self.pages.sort_by_key(|page| page.info.index);
+
+        Ok(())
+    }
+
+    /// Loads a manifest written by [`Manifest::write_json`]. Returns `None`
+    /// if there is no file at `path`.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        let data = match std::fs::read(path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(err).with_context(|| format!("error reading {}", path.display()))
+            }
+        };
+
+        serde_json::from_slice(&data)
+            .map(Some)
+            .with_context(|| format!("error parsing {}", path.display()))
+    }
+
+    /// Adds the pages of a previous manifest of the deck which were not
+    /// converted this time, unless they are past the end of the document.
+    pub fn merge(&mut self, previous: Manifest) {
+        let page_count = self.document.page_count;
+        let kept: Vec<_> = previous
+            .pages
+            .into_iter()
+            .filter(|page| {
+                page.info.index < page_count
+                    && !self
+                        .pages
+                        .iter()
+                        .any(|converted| converted.info.index == page.info.index)
+            })
+            .collect();
+
+        self.pages.extend(kept);
+        self.pages.sort_by_key(|page| page.info.index);
+    }
+
+    pub fn write_json(&self, path: &Path) -> Result<()> {
+        write_json(path, self).context("error writing manifest")
+    }
+}
+
+impl ManifestFile {
+    fn new(path: &Path, output_dir: &Path) -> Result<Self> {
+        let contents =
+            std::fs::read(path).with_context(|| format!("error reading {}", path.display()))?;
+        let sha256 = to_hex(&Sha256::digest(contents));
+        let path = path.strip_prefix(output_dir).unwrap_or(path);
+
+        Ok(Self {
+            path: path.to_string_lossy().into_owned(),
+            sha256,
+        })
+    }
+}
@@ -0,0 +1,151 @@
+// Copyright (C) 2024 Adrien Bustany <adrien@bustany.org>
+
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+
+const LOCK_FILENAME: &str = ".pdf2svgslides.lock";
+
+/// How files that already exist in the output directory are handled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverwritePolicy {
+    /// Replace existing files.
+    #[default]
+    Overwrite,
+    /// Keep existing files, and do not generate them again.
+    SkipExisting,
+    /// Fail the conversion of the page.
+    Fail,
+}
+
+pub(crate) fn write_json(path: &Path, value: &impl Serialize) -> Result<()> {
+    let json = serde_json::to_vec_pretty(value)
+        .with_context(|| format!("error serializing {}", path.display()))?;
+    write_file(path, &json)
+}
+
+/// Writes a file atomically: the contents are written to a temporary file in
+/// the same directory, which then gets renamed, so that readers never see a
+/// partially written file.
+pub(crate) fn write_file(path: &Path, contents: &[u8]) -> Result<()> {
+    let tmp = write_temp(path, contents)?;
+    std::fs::rename(&tmp, path)
+        .inspect_err(|_| {
+            let _ = std::fs::remove_file(&tmp);
+        })
+        .with_context(|| format!("error writing file {}", path.display()))
+}
+
+/// Same as [`write_file`], but fails if `path` already exists.
+pub(crate) fn write_new_file(path: &Path, contents: &[u8]) -> Result<()> {
+    let tmp = write_temp(path, contents)?;
+    // unlike rename, linking never replaces the destination
+    let res = match std::fs::hard_link(&tmp, path) {
+        // e.g. on FAT or SMB file systems, which do not support hard links
+        Err(err) if err.kind() != std::io::ErrorKind::AlreadyExists => rename_new(&tmp, path),
+        res => res,
+    };
+    let _ = std::fs::remove_file(&tmp);
+
+    match res {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+            bail!("file {} already exists", path.display())
+        }
+        Err(err) => Err(err).with_context(|| format!("error writing file {}", path.display())),
+    }
+}
+
+/// Renames `tmp` to `path` if `path` does not exist. Unlike with a hard link,
+/// readers may see an empty file at `path` until the rename.
+fn rename_new(tmp: &Path, path: &Path) -> std::io::Result<()> {
+    // reserves the destination, failing if it exists
+    File::options().write(true).create_new(true).open(path)?;
+
+    std::fs::rename(tmp, path).inspect_err(|_| {
+        let _ = std::fs::remove_file(path);
+    })
+}
+
+/// Writes a file according to `policy`. Returns whether the file was
+/// written, which is not the case when it exists and gets skipped.
+pub(crate) fn write_output(path: &Path, contents: &[u8], policy: OverwritePolicy) -> Result<bool> {
+    match policy {
+        OverwritePolicy::Overwrite => write_file(path, contents)?,
+        OverwritePolicy::SkipExisting if path.exists() => return Ok(false),
+        OverwritePolicy::SkipExisting | OverwritePolicy::Fail => write_new_file(path, contents)?,
+    }
+
+    Ok(true)
+}
+
+fn write_temp(path: &Path, contents: &[u8]) -> Result<PathBuf> {
+    // distinguishes the temporary files of different threads
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let name = path
+        .file_name()
+        .with_context(|| format!("invalid file name {}", path.display()))?;
+    let tmp = path.with_file_name(format!(
+        ".{}.{}-{}.tmp",
+        name.to_string_lossy(),
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let res = File::create(&tmp)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .with_context(|| format!("error writing file {}", path.display()));
+
+    if res.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+
+    res.map(|_| tmp)
+}
+
+/// Creates `dir` if needed, and takes an exclusive lock on it, held until the
+/// returned file is closed. The lock is released by the OS if the process
+/// dies.
+pub(crate) fn lock_dir(dir: &Path) -> Result<File> {
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("error creating output directory {}", dir.display()))?;
+
+    let path = dir.join(LOCK_FILENAME);
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("error creating lock file {}", path.display()))?;
+
+    // not File::try_lock, which needs Rust 1.89
+    match fs4::FileExt::try_lock(&file) {
+        Ok(()) => Ok(file),
+        Err(fs4::TryLockError::WouldBlock) => bail!(
+            "output directory {} is in use by another conversion",
+            dir.display()
+        ),
+        Err(fs4::TryLockError::Error(err)) => {
+            Err(err).with_context(|| format!("error locking {}", path.display()))
+        }
+    }
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    use std::fmt::Write;
+
+    bytes
+        .iter()
+        .fold(String::with_capacity(bytes.len() * 2), |mut hex, b| {
+            let _ = write!(hex, "{:02x}", b);
+            hex
+        })
+}
@@ -0,0 +1,266 @@
+// Copyright (C) 2024 Adrien Bustany <adrien@bustany.org>
+
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Result};
+
+/// A selection of pages, written as a comma separated list of page numbers
+/// and ranges, e.g. `1,3-5,10-`.
+///
+/// Page numbers start at 1. Negative numbers count from the end of the
+/// document, `-1` being the last page. A range with no end (`10-`) extends to
+/// the last page.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PageSelection {
+    // an empty list selects every page
+    items: Vec<Item>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Item {
+    Single(i32),
+    Range(i32, Option<i32>),
+}
+
+impl PageSelection {
+    /// Selects every page of the document.
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// Returns the zero-based indices of the selected pages, in selection
+    /// order and without duplicates.
+    pub fn resolve(&self, page_count: i32) -> Result<Vec<i32>> {
+        if self.items.is_empty() {
+            return Ok((0..page_count).collect());
+        }
+
+        let mut indices = Vec::new();
+
+        for item in &self.items {
+            let (start, end) = match *item {
+                Item::Single(n) => {
+                    let i = resolve_number(n, page_count)?;
+                    (i, i)
+                }
+                Item::Range(start, end) => {
+                    let start = resolve_number(start, page_count)?;
+                    let end = match end {
+                        Some(end) => resolve_number(end, page_count)?,
+                        None => page_count - 1,
+                    };
+
+                    if start > end {
+                        bail!(
+                            "page range {} is empty (it starts at page {} and ends at page {})",
+                            item,
+                            start + 1,
+                            end + 1
+                        );
+                    }
+
+                    (start, end)
+                }
+            };
+
+            for i in start..=end {
+                if !indices.contains(&i) {
+                    indices.push(i);
+                }
+            }
+        }
+
+        Ok(indices)
+    }
+}
+
+fn resolve_number(n: i32, page_count: i32) -> Result<i32> {
+    if n > 0 && n <= page_count {
+        return Ok(n - 1);
+    }
+
+    if n < 0 && n >= -page_count {
+        return Ok(page_count + n);
+    }
+
+    bail!(
+        "page {} is out of range (the document has {} page{})",
+        n,
+        page_count,
+        if page_count == 1 { "" } else { "s" }
+    );
+}
+
+impl FromStr for PageSelection {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let items = s
+            .split(',')
+            .map(|item| item.trim().parse())
+            .collect::<Result<_>>()?;
+
+        Ok(Self { items })
+    }
+}
+
+impl FromStr for Item {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.is_empty() {
+            bail!("empty page selection");
+        }
+
+        // skip the first character so that a leading minus sign is not taken
+        // as a range separator
+        let Some((sep, _)) = s.char_indices().skip(1).find(|&(_, c)| c == '-') else {
+            return Ok(Item::Single(parse_number(s)?));
+        };
+
+        let (start, end) = (&s[..sep], &s[sep + 1..]);
+        let end = if end.is_empty() {
+            None
+        } else {
+            Some(parse_number(end)?)
+        };
+
+        Ok(Item::Range(parse_number(start)?, end))
+    }
+}
+
+fn parse_number(s: &str) -> Result<i32> {
+    let n = s
+        .parse()
+        .with_context(|| format!("invalid page number \"{}\"", s))?;
+
+    if n == 0 {
+        bail!("invalid page number 0 (page numbers start at 1)");
+    }
+
+    Ok(n)
+}
+
+impl std::fmt::Display for Item {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Item::Single(n) => write!(f, "{}", n),
+            Item::Range(start, Some(end)) => write!(f, "{}-{}", start, end),
+            Item::Range(start, None) => write!(f, "{}-", start),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(selection: &str, page_count: i32) -> Result<Vec<i32>> {
+        selection.parse::<PageSelection>()?.resolve(page_count)
+    }
+
+    fn error(selection: &str, page_count: i32) -> String {
+        resolve(selection, page_count).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn parse_items() {
+        let parse = |s: &str| s.parse::<PageSelection>().unwrap().items;
+
+        assert_eq!(parse("3"), [Item::Single(3)]);
+        assert_eq!(parse("-3"), [Item::Single(-3)]);
+        assert_eq!(parse("3-5"), [Item::Range(3, Some(5))]);
+        assert_eq!(parse("3-"), [Item::Range(3, None)]);
+        assert_eq!(parse("-3-"), [Item::Range(-3, None)]);
+        assert_eq!(parse("3--1"), [Item::Range(3, Some(-1))]);
+        assert_eq!(parse("-3--1"), [Item::Range(-3, Some(-1))]);
+        assert_eq!(
+            parse(" 1 , 4-6 "),
+            [Item::Single(1), Item::Range(4, Some(6))]
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |s: &str| s.parse::<PageSelection>().unwrap_err().to_string();
+
+        assert_eq!(error(""), "empty page selection");
+        assert_eq!(error("1,,2"), "empty page selection");
+        assert_eq!(error("1,"), "empty page selection");
+        assert_eq!(error("--1"), "invalid page number \"-\"");
+        assert_eq!(error("1-2-3"), "invalid page number \"2-3\"");
+        assert_eq!(error("a"), "invalid page number \"a\"");
+        assert_eq!(error("1-b"), "invalid page number \"b\"");
+        assert_eq!(
+            error("0"),
+            "invalid page number 0 (page numbers start at 1)"
+        );
+        assert_eq!(
+            error("0-3"),
+            "invalid page number 0 (page numbers start at 1)"
+        );
+        assert_eq!(
+            error("-0"),
+            "invalid page number 0 (page numbers start at 1)"
+        );
+    }
+
+    #[test]
+    fn resolve_all() {
+        assert_eq!(PageSelection::all().resolve(3).unwrap(), [0, 1, 2]);
+        assert!(PageSelection::all().resolve(0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn resolve_items() {
+        assert_eq!(resolve("1", 10).unwrap(), [0]);
+        assert_eq!(resolve("10", 10).unwrap(), [9]);
+        assert_eq!(resolve("-1", 10).unwrap(), [9]);
+        assert_eq!(resolve("-10", 10).unwrap(), [0]);
+        assert_eq!(resolve("3-5", 10).unwrap(), [2, 3, 4]);
+        assert_eq!(resolve("8-", 10).unwrap(), [7, 8, 9]);
+        assert_eq!(resolve("-3-", 10).unwrap(), [7, 8, 9]);
+        assert_eq!(resolve("-1-", 10).unwrap(), [9]);
+        assert_eq!(resolve("8--1", 10).unwrap(), [7, 8, 9]);
+        assert_eq!(resolve("5-5", 10).unwrap(), [4]);
+    }
+
+    #[test]
+    fn resolve_keeps_order_without_duplicates() {
+        assert_eq!(resolve("5,1-3", 10).unwrap(), [4, 0, 1, 2]);
+        assert_eq!(resolve("2,1-3,3,-8", 10).unwrap(), [1, 0, 2]);
+        assert_eq!(resolve("-1,10", 10).unwrap(), [9]);
+    }
+
+    #[test]
+    fn resolve_errors() {
+        assert_eq!(
+            error("5-3", 10),
+            "page range 5-3 is empty (it starts at page 5 and ends at page 3)"
+        );
+        assert_eq!(
+            error("-1--3", 10),
+            "page range -1--3 is empty (it starts at page 10 and ends at page 8)"
+        );
+        assert_eq!(
+            error("11", 10),
+            "page 11 is out of range (the document has 10 pages)"
+        );
+        assert_eq!(
+            error("-11", 10),
+            "page -11 is out of range (the document has 10 pages)"
+        );
+        assert_eq!(
+            error("1-11", 10),
+            "page 11 is out of range (the document has 10 pages)"
+        );
+        assert_eq!(
+            error("2", 1),
+            "page 2 is out of range (the document has 1 page)"
+        );
+        assert_eq!(
+            error("1-", 0),
+            "page 1 is out of range (the document has 0 pages)"
+        );
+    }
+}
@@ -0,0 +1,121 @@
+// Copyright (C) 2024 Adrien Bustany <adrien@bustany.org>
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::JoinHandle;
+
+use anyhow::{anyhow, Result};
+
+use crate::convert::ConverterSeed;
+use crate::PageOutput;
+
+/// Iterator over pages converted by a pool of worker threads, yielding
+/// results in selection order.
+pub(crate) struct ParallelPages {
+    pages: Arc<[i32]>,
+    // position in `pages` of the next result to yield
+    next: usize,
+    // results received ahead of their turn, by position
+    pending: BTreeMap<usize, Result<PageOutput>>,
+    receiver: mpsc::Receiver<(usize, Result<PageOutput>)>,
+    cancelled: Arc<AtomicBool>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ParallelPages {
+    pub(crate) fn start(seed: &ConverterSeed, pages: Vec<i32>, jobs: usize) -> Self {
+        let pages: Arc<[i32]> = pages.into();
+        let claimed = Arc::new(AtomicUsize::new(0));
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::channel();
+
+        let workers = (0..jobs)
+            .map(|_| {
+                let worker = Worker {
+                    seed: seed.clone(),
+                    pages: pages.clone(),
+                    claimed: claimed.clone(),
+                    cancelled: cancelled.clone(),
+                    sender: sender.clone(),
+                };
+                std::thread::spawn(move || worker.run())
+            })
+            .collect();
+
+        Self {
+            pages,
+            next: 0,
+            pending: BTreeMap::new(),
+            receiver,
+            cancelled,
+            workers,
+        }
+    }
+}
+
+impl Iterator for ParallelPages {
+    type Item = (i32, Result<PageOutput>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let &index = self.pages.get(self.next)?;
+
+        let result = loop {
+            if let Some(result) = self.pending.remove(&self.next) {
+                break result;
+            }
+
+            match self.receiver.recv() {
+                Ok((pos, result)) => {
+                    self.pending.insert(pos, result);
+                }
+                // all the workers exited without converting this page, which
+                // only happens if one of them panicked
+                Err(_) => break Err(anyhow!("page {} was not converted", index + 1)),
+            }
+        };
+
+        self.next += 1;
+        Some((index, result))
+    }
+}
+
+impl Drop for ParallelPages {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+struct Worker {
+    seed: ConverterSeed,
+    pages: Arc<[i32]>,
+    claimed: Arc<AtomicUsize>,
+    cancelled: Arc<AtomicBool>,
+    sender: mpsc::Sender<(usize, Result<PageOutput>)>,
+}
+
+impl Worker {
+    fn run(self) {
+        let converter = self.seed.open();
+
+        while !self.cancelled.load(Ordering::Relaxed) {
+            let pos = self.claimed.fetch_add(1, Ordering::Relaxed);
+            let Some(&index) = self.pages.get(pos) else {
+                break;
+            };
+
+            let result = match &converter {
+                Ok(converter) => converter.write_page(index),
+                Err(err) => Err(anyhow!("{:#}", err)),
+            };
+
+            if self.sender.send((pos, result)).is_err() {
+                break;
+            }
+        }
+    }
+}
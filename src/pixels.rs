@@ -0,0 +1,154 @@
+// Copyright (C) 2024 Adrien Bustany <adrien@bustany.org>
+
+// Cairo stores each pixel of Rgb24 and ARgb32 image surfaces as a
+// native-endian 32-bit word (alpha in the high byte, premultiplied into the
+// color components for ARgb32), and rows may be padded: row y starts at byte
+// y * stride.
+
+use anyhow::{ensure, Result};
+
+/// Returns the alpha, red, green and blue components of the pixel at `x`,
+/// `y`.
+pub(crate) fn argb(data: &[u8], stride: usize, x: usize, y: usize) -> [u8; 4] {
+    let i = y * stride + x * 4;
+    u32::from_ne_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]).to_be_bytes()
+}
+
+/// Converts surface pixels in place to packed 8-bit RGBA with straight
+/// alpha if `alpha` is set, or to packed RGB otherwise (ignoring the alpha
+/// byte, as for `Rgb24` surfaces), and returns the converted pixels, which
+/// start at the beginning of `data`.
+pub(crate) fn pack(
+    data: &mut [u8],
+    width: usize,
+    height: usize,
+    stride: usize,
+    alpha: bool,
+) -> Result<&[u8]> {
+    ensure!(stride >= width * 4, "invalid stride {}", stride);
+    ensure!(
+        height == 0 || data.len() >= (height - 1) * stride + width * 4,
+        "image data too short"
+    );
+
+    let channels = if alpha { 4 } else { 3 };
+    // packed pixels are never written past the ones left to read, as they
+    // are at most as large and rows are at most as long
+    let mut j = 0;
+
+    for y in 0..height {
+        for x in 0..width {
+            let [a, r, g, b] = argb(data, stride, x, y);
+
+            if alpha {
+                data[j..j + 4].copy_from_slice(&[
+                    unpremultiply(r, a),
+                    unpremultiply(g, a),
+                    unpremultiply(b, a),
+                    a,
+                ]);
+            } else {
+                data[j..j + 3].copy_from_slice(&[r, g, b]);
+            }
+
+            j += channels;
+        }
+    }
+
+    Ok(&data[..j])
+}
+
+/// Converts a color component from premultiplied to straight alpha.
+pub(crate) fn unpremultiply(v: u8, alpha: u8) -> u8 {
+    match alpha {
+        0 => 0,
+        _ => ((u16::from(v) * 255 + u16::from(alpha) / 2) / u16::from(alpha)) as u8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PADDING: u8 = 0xee;
+
+    /// Builds a surface of `width` by `height` pixels, each having distinct
+    /// components so that overwriting a pixel before it gets read changes the
+    /// output, and rows padded to `stride` bytes.
+    fn surface(width: usize, height: usize, stride: usize) -> (Vec<u8>, Vec<[u8; 4]>) {
+        let mut data = vec![PADDING; stride * height];
+        let mut pixels = Vec::new();
+
+        for y in 0..height {
+            for x in 0..width {
+                let n = (y * width + x) as u8;
+                // premultiplied components are at most the alpha
+                let argb = [200 + n, 10 + n, 100 + n, 190 + n];
+                let i = y * stride + x * 4;
+                data[i..i + 4].copy_from_slice(&u32::from_be_bytes(argb).to_ne_bytes());
+                pixels.push(argb);
+            }
+        }
+
+        (data, pixels)
+    }
+
+    #[test]
+    fn pack_rgb_padded() {
+        for (width, height, stride) in [(3, 4, 12), (3, 4, 20), (1, 5, 64), (5, 1, 24)] {
+            let (mut data, pixels) = surface(width, height, stride);
+            let expected: Vec<u8> = pixels.iter().flat_map(|&[_, r, g, b]| [r, g, b]).collect();
+
+            let packed = pack(&mut data, width, height, stride, false).unwrap();
+            assert_eq!(packed, expected, "{}x{}, stride {}", width, height, stride);
+        }
+    }
+
+    #[test]
+    fn pack_rgba_padded() {
+        for (width, height, stride) in [(3, 4, 12), (3, 4, 20), (1, 5, 64), (5, 1, 24)] {
+            let (mut data, pixels) = surface(width, height, stride);
+            let expected: Vec<u8> = pixels
+                .iter()
+                .flat_map(|&[a, r, g, b]| {
+                    [
+                        unpremultiply(r, a),
+                        unpremultiply(g, a),
+                        unpremultiply(b, a),
+                        a,
+                    ]
+                })
+                .collect();
+
+            let packed = pack(&mut data, width, height, stride, true).unwrap();
+            assert_eq!(packed, expected, "{}x{}, stride {}", width, height, stride);
+        }
+    }
+
+    #[test]
+    fn pack_ignores_padding_past_last_row() {
+        // the last row does not need its padding
+        let (mut data, _) = surface(2, 2, 16);
+        data.truncate(16 + 8);
+
+        assert_eq!(pack(&mut data, 2, 2, 16, false).unwrap().len(), 12);
+    }
+
+    #[test]
+    fn pack_errors() {
+        let (mut data, _) = surface(3, 2, 12);
+
+        assert!(pack(&mut data, 3, 2, 8, false).is_err());
+        assert!(pack(&mut data, 3, 3, 12, false).is_err());
+        assert!(pack(&mut data, 0, 0, 0, true).unwrap().is_empty());
+    }
+
+    #[test]
+    fn unpremultiply_components() {
+        assert_eq!(unpremultiply(0, 0), 0);
+        assert_eq!(unpremultiply(128, 255), 128);
+        assert_eq!(unpremultiply(64, 128), 128);
+        assert_eq!(unpremultiply(100, 100), 255);
+        assert_eq!(unpremultiply(1, 3), 85);
+    }
+}
@@ -0,0 +1,73 @@
+// Copyright (C) 2024 Adrien Bustany <adrien@bustany.org>
+
+use anyhow::{bail, Result};
+
+use crate::thumbnail::{Layout, ThumbnailFit, ThumbnailSize};
+use crate::ThumbnailFormat;
+
+/// Export of each page as a full-size raster image, for targets that do not
+/// support SVG.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RasterExport {
+    pub resolution: RasterResolution,
+    /// Image format, with the same settings as thumbnails.
+    pub format: ThumbnailFormat,
+}
+
+/// Resolution of raster images.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RasterResolution {
+    /// Dots per inch, a page of 8.5 by 11 inches being 850 by 1100 pixels at
+    /// 100 DPI.
+    Dpi(f64),
+    /// Width in pixels, the height following the aspect ratio of the page.
+    Width(u32),
+}
+
+impl RasterResolution {
+    /// Checks that the resolution is positive.
+    pub(crate) fn check(&self) -> Result<()> {
+        match *self {
+            Self::Dpi(dpi) if !(dpi.is_finite() && dpi > 0.) => {
+                bail!("invalid resolution {} DPI", dpi)
+            }
+            Self::Width(0) => bail!("image width must not be zero"),
+            _ => Ok(()),
+        }
+    }
+
+    /// Computes how a page of `page_width` by `page_height` points gets
+    /// placed into its raster image.
+    pub(crate) fn layout(&self, page_width: f64, page_height: f64) -> Result<Layout> {
+        self.check()?;
+
+        match *self {
+            Self::Dpi(dpi) => {
+                if !(page_width > 0. && page_height > 0.) {
+                    bail!("invalid page size {}x{}", page_width, page_height);
+                }
+
+                // PDF units are points, 1/72 of an inch
+                let scale = dpi / 72.;
+                let scaled = |v: f64| -> Result<u32> {
+                    let v = (v * scale).round().max(1.);
+                    if v > f64::from(i32::MAX) {
+                        bail!("image too large");
+                    }
+                    Ok(v as u32)
+                };
+
+                Ok(Layout {
+                    scale,
+                    x: 0.,
+                    y: 0.,
+                    width: scaled(page_width)?,
+                    height: scaled(page_height)?,
+                })
+            }
+            Self::Width(width) => {
+                ThumbnailFit::Width.layout(page_width, page_height, ThumbnailSize::from(width))
+            }
+        }
+    }
+}
@@ -0,0 +1,279 @@
+// Copyright (C) 2024 Adrien Bustany <adrien@bustany.org>
+
+use std::borrow::Cow;
+
+use anyhow::{Context, Result};
+
+use crate::background::Background;
+use crate::geometry::PageArea;
+use crate::pixels;
+use crate::resample;
+use crate::thumbnail::Layout;
+use crate::{ConvertOptions, SvgUnit, SvgVersion, ThumbnailFormat, VectorFormat};
+
+/// Renders the page once into a recording surface, which then gets replayed
+/// into each output without having poppler interpret the page again.
+pub(crate) fn record_page(
+    page: &poppler::Page,
+    area: &PageArea,
+) -> Result<cairo::RecordingSurface> {
+    let extents = cairo::Rectangle::new(0., 0., area.width, area.height);
+    let surface = cairo::RecordingSurface::create(cairo::Content::ColorAlpha, Some(extents))
+        .context("error creating recording surface")?;
+    let ctx = cairo::Context::new(&surface).context("error creating Cairo context")?;
+    area.apply(&ctx);
+    page.render_for_printing(&ctx);
+    ctx.status().context("error rendering page")?;
+
+    Ok(surface)
+}
+
+fn replay(recording: &cairo::RecordingSurface, ctx: &cairo::Context) -> Result<()> {
+    ctx.set_source_surface(recording, 0., 0.)
+        .context("error setting source surface")?;
+    ctx.paint().context("error painting page")?;
+    ctx.status().context("error painting page")?;
+
+    Ok(())
+}
+
+/// Lowest fallback resolution picked to fit the SVG size limit.
+const MIN_FALLBACK_RESOLUTION: f64 = 72.;
+
+/// Renders the page as an SVG document, returned as bytes.
+///
+/// With an SVG size limit, documents above it are rendered again at lower
+/// fallback resolutions until they fit, or stop shrinking.
+pub(crate) fn render_svg(
+    recording: &cairo::RecordingSurface,
+    area: &PageArea,
+    options: &ConvertOptions,
+) -> Result<Vec<u8>> {
+    let mut resolution = options.fallback_resolution;
+    let mut svg = render_svg_at(recording, area, resolution, options)?;
+    let Some(limit) = options.svg_size_limit else {
+        return Ok(svg);
+    };
+
+    while svg.len() as u64 > limit && resolution > MIN_FALLBACK_RESOLUTION {
+        // fallback images grow with the square of the resolution, aim a bit
+        // lower as the rest of the document does not shrink
+        let ratio = (limit as f64 / svg.len() as f64).sqrt();
+        resolution = (resolution * ratio * 0.9).max(MIN_FALLBACK_RESOLUTION);
+
+        let smaller = render_svg_at(recording, area, resolution, options)?;
+        if smaller.len() >= svg.len() {
+            // nothing got rasterized
+            break;
+        }
+        svg = smaller;
+    }
+
+    Ok(svg)
+}
+
+fn render_svg_at(
+    recording: &cairo::RecordingSurface,
+    area: &PageArea,
+    fallback_resolution: f64,
+    options: &ConvertOptions,
+) -> Result<Vec<u8>> {
+    // the surface coordinates are in the document unit
+    let scale = match options.svg_width {
+        Some(width) => f64::from(width) / area.width,
+        None => options.svg_unit.per_point(),
+    };
+    let mut surface =
+        cairo::SvgSurface::for_stream(area.width * scale, area.height * scale, Vec::<u8>::new())
+            .context("error creating SVG surface")?;
+    surface.restrict(match options.svg_version {
+        SvgVersion::V1_1 => cairo::SvgVersion::_1_1,
+        SvgVersion::V1_2 => cairo::SvgVersion::_1_2,
+    });
+    surface.set_document_unit(match options.svg_unit {
+        SvgUnit::Pt => cairo::SvgUnit::Pt,
+        SvgUnit::Px => cairo::SvgUnit::Px,
+        SvgUnit::Mm => cairo::SvgUnit::Mm,
+        SvgUnit::User => cairo::SvgUnit::User,
+    });
+
+    render_document(&surface, recording, scale, fallback_resolution, options)
+        .context("error writing SVG data")
+}
+
+/// Renders the page as a single-page PDF, PostScript or EPS document,
+/// returned as bytes.
+pub(crate) fn render_vector(
+    recording: &cairo::RecordingSurface,
+    area: &PageArea,
+    format: VectorFormat,
+    options: &ConvertOptions,
+) -> Result<Vec<u8>> {
+    let surface = match format {
+        VectorFormat::Pdf => {
+            cairo::PdfSurface::for_stream(area.width, area.height, Vec::<u8>::new())
+                .map(|surface| (*surface).clone())
+        }
+        VectorFormat::Ps | VectorFormat::Eps => {
+            cairo::PsSurface::for_stream(area.width, area.height, Vec::<u8>::new()).map(|surface| {
+                surface.set_eps(format == VectorFormat::Eps);
+                (*surface).clone()
+            })
+        }
+    }
+    .with_context(|| format!("error creating {} surface", format.name()))?;
+
+    render_document(
+        &surface,
+        recording,
+        1.,
+        options.fallback_resolution,
+        options,
+    )
+    .with_context(|| format!("error writing {} data", format.name()))
+}
+
+/// Replays the page, scaled by `scale`, into a vector surface writing to a
+/// `Vec<u8>` stream, and returns the stream contents.
+fn render_document(
+    surface: &cairo::Surface,
+    recording: &cairo::RecordingSurface,
+    scale: f64,
+    fallback_resolution: f64,
+    options: &ConvertOptions,
+) -> Result<Vec<u8>> {
+    // Cairo takes surface units for points when sizing fallback images
+    let fallback_resolution = fallback_resolution / scale;
+    surface.set_fallback_resolution(fallback_resolution, fallback_resolution);
+    {
+        let ctx = cairo::Context::new(surface).context("error creating Cairo context")?;
+        options
+            .background
+            .paint(&ctx)
+            .context("error painting background")?;
+        ctx.scale(scale, scale);
+        replay(recording, &ctx)?;
+    }
+
+    let stream = surface.finish_output_stream().map_err(|err| err.error)?;
+    let data = stream
+        .downcast::<Vec<u8>>()
+        .expect("surface stream is a Vec<u8>");
+
+    Ok(*data)
+}
+
+/// Renders a thumbnail of the page, placed according to `layout`, encoded in
+/// the configured format and returned as bytes.
+pub(crate) fn render_thumbnail(
+    recording: &cairo::RecordingSurface,
+    layout: &Layout,
+    options: &ConvertOptions,
+) -> Result<Vec<u8>> {
+    render_image(
+        recording,
+        layout,
+        &options.thumbnail_format,
+        options.thumbnail_supersampling,
+        options.sharpen_thumbnails,
+        options,
+    )
+}
+
+/// Renders a full-size raster image of the page, placed according to
+/// `layout`, encoded in `format` and returned as bytes.
+pub(crate) fn render_raster(
+    recording: &cairo::RecordingSurface,
+    layout: &Layout,
+    format: &ThumbnailFormat,
+    options: &ConvertOptions,
+) -> Result<Vec<u8>> {
+    render_image(recording, layout, format, 1, false, options)
+}
+
+/// Renders the page as an image, at `supersampling` times its size before
+/// downsampling it, optionally sharpened.
+fn render_image(
+    recording: &cairo::RecordingSurface,
+    layout: &Layout,
+    format: &ThumbnailFormat,
+    supersampling: u32,
+    sharpen: bool,
+    options: &ConvertOptions,
+) -> Result<Vec<u8>> {
+    let (image_width, image_height) = (layout.width, layout.height);
+    let transparent = options.background == Background::Transparent;
+    // rendered at a multiple of the image size, then downsampled
+    let supersampling = supersampling.max(1);
+    let (surface_width, surface_height) = (
+        image_width
+            .checked_mul(supersampling)
+            .context("width too big")?,
+        image_height
+            .checked_mul(supersampling)
+            .context("height too big")?,
+    );
+    let surface = cairo::ImageSurface::create(
+        if transparent {
+            cairo::Format::ARgb32
+        } else {
+            cairo::Format::Rgb24
+        },
+        i32::try_from(surface_width).context("width too big")?,
+        i32::try_from(surface_height).context("height too big")?,
+    )
+    .context("error creating surface")?;
+    surface.set_fallback_resolution(options.fallback_resolution, options.fallback_resolution);
+
+    {
+        let ctx = cairo::Context::new(&surface).context("error creating Cairo context")?;
+        options
+            .background
+            .paint(&ctx)
+            .context("error painting background")?;
+        ctx.scale(f64::from(supersampling), f64::from(supersampling));
+        ctx.translate(layout.x, layout.y);
+        ctx.scale(layout.scale, layout.scale);
+        replay(recording, &ctx)?;
+    } // drop context here so that we can access the surface afterwards
+
+    let stride = usize::try_from(surface.stride()).context("invalid stride")?;
+    let mut data = surface.take_data().context("error accessing image data")?;
+
+    let pixels = if supersampling > 1 {
+        Cow::Owned(resample::downsample(
+            &data,
+            surface_width,
+            surface_height,
+            stride,
+            image_width,
+            image_height,
+            transparent,
+        ))
+    } else {
+        Cow::Borrowed(pixels::pack(
+            &mut data,
+            image_width as usize,
+            image_height as usize,
+            stride,
+            transparent,
+        )?)
+    };
+
+    let pixels = if sharpen {
+        Cow::Owned(resample::sharpen(
+            pixels.into_owned(),
+            image_width,
+            image_height,
+            transparent,
+        ))
+    } else {
+        pixels
+    };
+
+    if transparent {
+        format.encode_rgba(&pixels, image_width, image_height)
+    } else {
+        format.encode_rgb(&pixels, image_width, image_height)
+    }
+}
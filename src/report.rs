@@ -0,0 +1,60 @@
+// Copyright (C) 2024 Adrien Bustany <adrien@bustany.org>
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::error::{PageError, PageStage};
+use crate::output::write_json;
+use crate::PageOutput;
+
+/// Outcome of the conversion of a set of pages, serializable to JSON.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Report {
+    /// One-based numbers of the pages that were converted.
+    pub converted: Vec<i32>,
+    /// One-based numbers of the converted pages whose files were written,
+    /// which excludes the pages left untouched by an incremental conversion.
+    pub regenerated: Vec<i32>,
+    pub failed: Vec<PageFailure>,
+}
+
+/// A page that failed to convert.
+#[derive(Clone, Debug, Serialize)]
+pub struct PageFailure {
+    /// One-based page number.
+    pub page: i32,
+    /// Step of the conversion that failed, if known.
+    pub stage: Option<PageStage>,
+    pub error: String,
+}
+
+impl Report {
+    /// Records the result of the conversion of the page at zero-based
+    /// `index`.
+    pub fn record(&mut self, index: i32, result: &Result<PageOutput>) {
+        match result {
+            Ok(output) => {
+                self.converted.push(index + 1);
+
+                if output.regenerated {
+                    self.regenerated.push(index + 1);
+                }
+            }
+            Err(err) => self.failed.push(PageFailure {
+                page: index + 1,
+                stage: err.downcast_ref::<PageError>().map(|e| e.stage),
+                error: format!("{:#}", err),
+            }),
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    pub fn write_json(&self, path: &Path) -> Result<()> {
+        write_json(path, self).context("error writing report")
+    }
+}
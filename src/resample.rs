@@ -0,0 +1,338 @@
+// Copyright (C) 2024 Adrien Bustany <adrien@bustany.org>
+
+use std::collections::VecDeque;
+use std::sync::OnceLock;
+
+use image::imageops;
+use image::{ImageBuffer, Rgb, Rgba};
+
+use crate::pixels;
+
+/// Downsamples the pixels of a Cairo image surface (see the pixels module)
+/// to `width` by `height` pixels, with a Lanczos filter applied in linear light.
+///
+/// Returns packed 8-bit RGBA pixels with straight alpha if `alpha` is set,
+/// or packed RGB pixels otherwise, in which case the source alpha is ignored
+/// (as for `Rgb24` surfaces).
+///
+/// The filter is applied to one axis after the other, streaming through the
+/// source rows: only the rows contributing to the current destination row
+/// are kept, resampled horizontally, so that memory use does not grow with
+/// the supersampling factor.
+pub(crate) fn downsample(
+    data: &[u8],
+    src_width: u32,
+    src_height: u32,
+    stride: usize,
+    width: u32,
+    height: u32,
+    alpha: bool,
+) -> Vec<u8> {
+    let to_linear = srgb_to_linear_table();
+    let columns = filters(src_width, width);
+    let rows = filters(src_height, height);
+
+    // source row in premultiplied linear RGBA
+    let mut line = vec![[0.; 4]; src_width as usize];
+    // horizontally resampled source rows, starting at row `first`
+    let mut window: VecDeque<Vec<[f32; 4]>> = VecDeque::new();
+    let mut first = 0;
+    let mut sum = vec![[0.; 4]; width as usize];
+
+    let channels = if alpha { 4 } else { 3 };
+    let mut pixels = Vec::with_capacity(width as usize * height as usize * channels);
+
+    for filter in &rows {
+        let done = (filter.start - first).min(window.len());
+        window.drain(..done);
+        first = filter.start;
+
+        while window.len() < filter.weights.len() {
+            let y = first + window.len();
+            for (x, pixel) in line.iter_mut().enumerate() {
+                *pixel = linear_pixel(pixels::argb(data, stride, x, y), alpha, to_linear);
+            }
+            window.push_back(columns.iter().map(|column| column.apply(&line)).collect());
+        }
+
+        sum.fill([0.; 4]);
+        for (row, &weight) in window.iter().zip(&filter.weights) {
+            for (total, pixel) in sum.iter_mut().zip(row) {
+                for (total, v) in total.iter_mut().zip(pixel) {
+                    *total += v * weight;
+                }
+            }
+        }
+
+        for &[r, g, b, a] in &sum {
+            // Lanczos overshoots around sharp edges
+            let a = if alpha { a.clamp(0., 1.) } else { 1. };
+            let a_u8 = (a * 255.).round() as u8;
+            if a_u8 == 0 {
+                pixels.extend_from_slice(&[0; 4]);
+                continue;
+            }
+
+            pixels.extend([r, g, b].map(|v| linear_to_srgb((v / a).clamp(0., 1.))));
+            if alpha {
+                pixels.push(a_u8);
+            }
+        }
+    }
+
+    pixels
+}
+
+/// Converts a surface pixel to premultiplied RGBA in linear light.
+fn linear_pixel([a, r, g, b]: [u8; 4], alpha: bool, to_linear: &[f32; 256]) -> [f32; 4] {
+    if !alpha {
+        return [
+            to_linear[r as usize],
+            to_linear[g as usize],
+            to_linear[b as usize],
+            1.,
+        ];
+    }
+
+    if a == 0 {
+        return [0.; 4];
+    }
+
+    // premultiplied in sRGB space, to premultiplied in linear space
+    let a_f = f32::from(a) / 255.;
+    let linear = |v: u8| to_linear[pixels::unpremultiply(v, a) as usize] * a_f;
+    [linear(r), linear(g), linear(b), a_f]
+}
+
+/// Source pixels contributing to a destination pixel along one axis, and
+/// their weights.
+struct Filter {
+    start: usize,
+    weights: Vec<f32>,
+}
+
+impl Filter {
+    fn apply(&self, line: &[[f32; 4]]) -> [f32; 4] {
+        let mut sum = [0.; 4];
+
+        for (pixel, &weight) in line[self.start..].iter().zip(&self.weights) {
+            for (total, v) in sum.iter_mut().zip(pixel) {
+                *total += v * weight;
+            }
+        }
+
+        sum
+    }
+}
+
+/// Computes the Lanczos3 filters resampling `src` pixels to `dst`, with the
+/// same sampling as the `image` crate.
+fn filters(src: u32, dst: u32) -> Vec<Filter> {
+    const SUPPORT: f32 = 3.;
+
+    let ratio = src as f32 / dst as f32;
+    let scale = ratio.max(1.);
+    let support = SUPPORT * scale;
+
+    (0..dst)
+        .map(|i| {
+            let center = (i as f32 + 0.5) * ratio;
+            let left = ((center - support).floor() as i64).clamp(0, i64::from(src) - 1);
+            let right = ((center + support).ceil() as i64).clamp(left + 1, i64::from(src));
+            // the kernel is centered on pixel centers
+            let center = center - 0.5;
+
+            let mut weights: Vec<f32> = (left..right)
+                .map(|j| lanczos3((j as f32 - center) / scale))
+                .collect();
+            let total: f32 = weights.iter().sum();
+            weights.iter_mut().for_each(|w| *w /= total);
+
+            Filter {
+                start: left as usize,
+                weights,
+            }
+        })
+        .collect()
+}
+
+fn lanczos3(x: f32) -> f32 {
+    let sinc = |x: f32| {
+        if x == 0. {
+            1.
+        } else {
+            let x = x * std::f32::consts::PI;
+            x.sin() / x
+        }
+    };
+
+    if x.abs() < 3. {
+        sinc(x) * sinc(x / 3.)
+    } else {
+        0.
+    }
+}
+
+/// Sharpens packed 8-bit RGB or RGBA pixels with an unsharp mask, to
+/// compensate for the softness of downsampled images.
+pub(crate) fn sharpen(pixels: Vec<u8>, width: u32, height: u32, alpha: bool) -> Vec<u8> {
+    const SIGMA: f32 = 0.6;
+    const THRESHOLD: i32 = 2;
+
+    if alpha {
+        let image = ImageBuffer::<Rgba<u8>, _>::from_raw(width, height, pixels)
+            .expect("buffer matches the image dimensions");
+        imageops::unsharpen(&image, SIGMA, THRESHOLD).into_raw()
+    } else {
+        let image = ImageBuffer::<Rgb<u8>, _>::from_raw(width, height, pixels)
+            .expect("buffer matches the image dimensions");
+        imageops::unsharpen(&image, SIGMA, THRESHOLD).into_raw()
+    }
+}
+
+fn srgb_to_linear_table() -> &'static [f32; 256] {
+    static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+
+    TABLE.get_or_init(|| {
+        std::array::from_fn(|i| {
+            let v = i as f32 / 255.;
+            if v <= 0.04045 {
+                v / 12.92
+            } else {
+                ((v + 0.055) / 1.055).powf(2.4)
+            }
+        })
+    })
+}
+
+fn linear_to_srgb(v: f32) -> u8 {
+    let v = if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1. / 2.4) - 0.055
+    };
+
+    (v * 255.).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::imageops::FilterType;
+
+    const PADDING: usize = 8;
+
+    /// Returns the pixels of a `width` by `height` surface filled with noise,
+    /// with padded rows, and its stride. Pixels are opaque unless `alpha` is
+    /// set.
+    fn surface(width: u32, height: u32, alpha: bool) -> (Vec<u8>, usize) {
+        let stride = width as usize * 4 + PADDING;
+        let mut data = vec![0xee; stride * height as usize];
+        let mut state = 0x2545_f491_u32;
+        let mut next = || {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (state >> 16) as u8
+        };
+
+        for y in 0..height as usize {
+            for x in 0..width as usize {
+                let a = match (alpha, next()) {
+                    (false, _) => 255,
+                    // plenty of fully transparent and opaque pixels
+                    (true, v) if v < 48 => 0,
+                    (true, v) if v > 200 => 255,
+                    (true, v) => v,
+                };
+                let premultiply = |v: u8| (u16::from(v) * u16::from(a) / 255) as u8;
+                let argb = [
+                    a,
+                    premultiply(next()),
+                    premultiply(next()),
+                    premultiply(next()),
+                ];
+                let i = y * stride + x * 4;
+                data[i..i + 4].copy_from_slice(&u32::from_be_bytes(argb).to_ne_bytes());
+            }
+        }
+
+        (data, stride)
+    }
+
+    /// Resamples the whole image in linear light with the `image` crate.
+    fn reference(
+        data: &[u8],
+        src_width: u32,
+        src_height: u32,
+        stride: usize,
+        width: u32,
+        height: u32,
+        alpha: bool,
+    ) -> Vec<u8> {
+        let to_linear = srgb_to_linear_table();
+        let source = ImageBuffer::from_fn(src_width, src_height, |x, y| {
+            let argb = pixels::argb(data, stride, x as usize, y as usize);
+            Rgba(linear_pixel(argb, alpha, to_linear))
+        });
+        let resized: ImageBuffer<Rgba<f32>, Vec<f32>> =
+            imageops::resize(&source, width, height, FilterType::Lanczos3);
+
+        let mut pixels = Vec::new();
+        for &Rgba([r, g, b, a]) in resized.pixels() {
+            let a = if alpha { a.clamp(0., 1.) } else { 1. };
+            let a_u8 = (a * 255.).round() as u8;
+            if a_u8 == 0 {
+                pixels.extend_from_slice(&[0; 4]);
+                continue;
+            }
+
+            pixels.extend([r, g, b].map(|v| linear_to_srgb((v / a).clamp(0., 1.))));
+            if alpha {
+                pixels.push(a_u8);
+            }
+        }
+
+        pixels
+    }
+
+    fn check(src_width: u32, src_height: u32, width: u32, height: u32, alpha: bool) {
+        let (data, stride) = surface(src_width, src_height, alpha);
+        let actual = downsample(&data, src_width, src_height, stride, width, height, alpha);
+        let expected = reference(&data, src_width, src_height, stride, width, height, alpha);
+
+        assert!(
+            actual == expected,
+            "{}x{} to {}x{} (alpha: {}) differs from the image crate",
+            src_width,
+            src_height,
+            width,
+            height,
+            alpha
+        );
+    }
+
+    #[test]
+    fn matches_image_crate() {
+        for alpha in [false, true] {
+            check(13, 9, 5, 2, alpha);
+            check(9, 41, 4, 7, alpha);
+            check(23, 17, 7, 5, alpha);
+            check(7, 7, 7, 7, alpha);
+            check(5, 3, 1, 1, alpha);
+        }
+    }
+
+    #[test]
+    fn filters_slide_over_rows() {
+        let starts = |src, dst| -> Vec<usize> {
+            filters(src, dst)
+                .iter()
+                .map(|filter| filter.start)
+                .collect()
+        };
+
+        // every destination row uses all 9 source rows
+        assert_eq!(starts(9, 2), [0, 0]);
+        // rows at the top are dropped from the window as it slides down
+        assert_eq!(starts(41, 7), [0, 0, 0, 2, 8, 14, 20]);
+    }
+}
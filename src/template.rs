@@ -0,0 +1,336 @@
+// Copyright (C) 2024 Adrien Bustany <adrien@bustany.org>
+
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+
+/// Template for the names of the generated files, without extension.
+///
+/// The following placeholders are supported:
+///
+/// - `{page}`: the page number, zero padded to at least 3 digits (more for
+///   documents with more than 999 pages)
+/// - `{label}`: the page label defined by the document, or the page number if
+///   the page has no label
+/// - `{stem}`: the name of the input file, without its extension
+/// - `{title}`: the first line of text of the page, or the page number if the
+///   page has no text
+///
+/// Literal braces are written `{{` and `}}`. The default template is
+/// `{page}`.
+///
+/// Pages getting the same name as an earlier page of the document, such as
+/// the overlays of a beamer frame sharing its label, get a `-2`, `-3`, ...
+/// suffix.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FilenameTemplate {
+    parts: Vec<Part>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Page,
+    Label,
+    Stem,
+    Title,
+}
+
+/// Values substituted into a [`FilenameTemplate`].
+pub(crate) struct TemplateValues<'a> {
+    pub index: i32,
+    pub page_count: i32,
+    pub label: Option<&'a str>,
+    pub stem: &'a str,
+    pub title: Option<&'a str>,
+}
+
+const MAX_TITLE_LENGTH: usize = 60;
+
+impl Default for FilenameTemplate {
+    fn default() -> Self {
+        Self {
+            parts: vec![Part::Page],
+        }
+    }
+}
+
+impl FilenameTemplate {
+    /// Whether the template needs the text of the page to be extracted.
+    pub(crate) fn uses_title(&self) -> bool {
+        self.parts.contains(&Part::Title)
+    }
+
+    /// Whether the names depend on the name of the input file.
+    pub(crate) fn uses_stem(&self) -> bool {
+        self.parts.contains(&Part::Stem)
+    }
+
+    pub(crate) fn render(&self, values: &TemplateValues) -> String {
+        let digits = values.page_count.max(1).ilog10() as usize + 1;
+        let page_number = format!("{:0width$}", values.index + 1, width = digits.max(3));
+        let mut name = String::new();
+
+        for part in &self.parts {
+            match part {
+                Part::Literal(s) => name.push_str(s),
+                Part::Page => name.push_str(&page_number),
+                Part::Label => push_or(&mut name, values.label.map(sanitize), &page_number),
+                Part::Stem => push_or(&mut name, Some(sanitize(values.stem)), &page_number),
+                Part::Title => {
+                    let title = values.title.map(|title| {
+                        sanitize(&title.chars().take(MAX_TITLE_LENGTH).collect::<String>())
+                    });
+                    push_or(&mut name, title, &page_number)
+                }
+            }
+        }
+
+        name
+    }
+}
+
+/// Appends `value` to `name`, or `fallback` if there is no value or if
+/// nothing of it was left by [`sanitize`].
+fn push_or(name: &mut String, value: Option<String>, fallback: &str) {
+    match value.filter(|value| !value.is_empty()) {
+        Some(value) => name.push_str(&value),
+        None => name.push_str(fallback),
+    }
+}
+
+/// Adds a `-2`, `-3`, ... suffix to the names already used by earlier
+/// pages, ignoring case for case-insensitive file systems. Suffixed names
+/// never collide with the name of another page.
+pub(crate) fn disambiguate(names: &mut [String]) {
+    let mut taken: HashSet<String> = names.iter().map(|name| name.to_lowercase()).collect();
+    let mut seen = HashSet::new();
+
+    for name in names.iter_mut() {
+        if seen.insert(name.to_lowercase()) {
+            continue;
+        }
+
+        let (mut n, mut candidate) = (2, format!("{}-2", name));
+        while !taken.insert(candidate.to_lowercase()) {
+            n += 1;
+            candidate = format!("{}-{}", name, n);
+        }
+        *name = candidate;
+    }
+}
+
+/// Makes a value safe to use in a filename, by replacing anything but
+/// letters, digits, `-`, `_`, `.` and `+` with underscores.
+pub(crate) fn sanitize(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+
+    for c in s.trim().chars() {
+        let c = if c.is_alphanumeric() || "-_.+".contains(c) {
+            c
+        } else {
+            '_'
+        };
+
+        if c == '_' && out.ends_with('_') {
+            continue;
+        }
+
+        out.push(c);
+    }
+
+    out.trim_matches(|c| c == '_' || c == '.').to_owned()
+}
+
+impl FromStr for FilenameTemplate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' => {
+                    let mut name = String::new();
+
+                    loop {
+                        match chars.next() {
+                            Some('{') if name.is_empty() => {
+                                literal.push('{');
+                                break;
+                            }
+                            Some('}') => {
+                                if !literal.is_empty() {
+                                    parts.push(Part::Literal(std::mem::take(&mut literal)));
+                                }
+
+                                parts.push(match name.as_str() {
+                                    "page" => Part::Page,
+                                    "label" => Part::Label,
+                                    "stem" => Part::Stem,
+                                    "title" => Part::Title,
+                                    _ => bail!("unknown placeholder {{{}}}", name),
+                                });
+                                break;
+                            }
+                            Some(c) => name.push(c),
+                            None => bail!("unterminated placeholder {{{}", name),
+                        }
+                    }
+                }
+                '}' => {
+                    if chars.next() != Some('}') {
+                        bail!("unmatched '}}' (use '}}}}' for a literal brace)");
+                    }
+
+                    literal.push('}');
+                }
+                '/' | '\\' => bail!("the template must not contain path separators"),
+                c => literal.push(c),
+            }
+        }
+
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+
+        if parts.is_empty() {
+            bail!("the template is empty");
+        }
+
+        Ok(Self { parts })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values<'a>(
+        index: i32,
+        label: Option<&'a str>,
+        title: Option<&'a str>,
+    ) -> TemplateValues<'a> {
+        TemplateValues {
+            index,
+            page_count: 12,
+            label,
+            stem: "deck",
+            title,
+        }
+    }
+
+    fn render(template: &str, values: &TemplateValues) -> String {
+        template.parse::<FilenameTemplate>().unwrap().render(values)
+    }
+
+    #[test]
+    fn parse() {
+        let parts = |s: &str| s.parse::<FilenameTemplate>().unwrap().parts;
+
+        assert_eq!(parts("{page}"), [Part::Page]);
+        assert_eq!(
+            parts("{stem}-{label}_{title}"),
+            [
+                Part::Stem,
+                Part::Literal("-".into()),
+                Part::Label,
+                Part::Literal("_".into()),
+                Part::Title,
+            ]
+        );
+        assert_eq!(
+            parts("{{{page}}}"),
+            [
+                Part::Literal("{".into()),
+                Part::Page,
+                Part::Literal("}".into()),
+            ]
+        );
+        assert_eq!(parts("a{{b}}c"), [Part::Literal("a{b}c".into())]);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |s: &str| s.parse::<FilenameTemplate>().unwrap_err().to_string();
+
+        assert_eq!(error(""), "the template is empty");
+        assert_eq!(error("{page"), "unterminated placeholder {page");
+        assert_eq!(error("slide-{"), "unterminated placeholder {");
+        assert_eq!(error("{pages}"), "unknown placeholder {pages}");
+        assert_eq!(error("{}"), "unknown placeholder {}");
+        assert_eq!(
+            error("page}"),
+            "unmatched '}' (use '}}' for a literal brace)"
+        );
+        assert_eq!(
+            error("{page}}"),
+            "unmatched '}' (use '}}' for a literal brace)"
+        );
+        assert_eq!(
+            error("a/{page}"),
+            "the template must not contain path separators"
+        );
+        assert_eq!(
+            error("a\\{page}"),
+            "the template must not contain path separators"
+        );
+    }
+
+    #[test]
+    fn render_placeholders() {
+        let v = values(4, Some("iv"), Some("Our Roadmap: 2025"));
+
+        assert_eq!(render("{page}", &v), "005");
+        assert_eq!(render("{label}", &v), "iv");
+        assert_eq!(render("{stem}-{page}", &v), "deck-005");
+        assert_eq!(render("{title}", &v), "Our_Roadmap_2025");
+    }
+
+    #[test]
+    fn render_pads_page_numbers() {
+        let v = TemplateValues {
+            page_count: 1200,
+            ..values(4, None, None)
+        };
+
+        assert_eq!(render("{page}", &v), "0005");
+    }
+
+    #[test]
+    fn render_falls_back_to_page_number() {
+        assert_eq!(render("{label}", &values(4, None, None)), "005");
+        assert_eq!(render("{label}", &values(4, Some("—"), None)), "005");
+        assert_eq!(render("{label}", &values(4, Some("..."), None)), "005");
+        assert_eq!(render("{label}", &values(4, Some("  "), None)), "005");
+        assert_eq!(render("{title}", &values(4, None, None)), "005");
+        assert_eq!(render("{title}", &values(4, None, Some("???"))), "005");
+
+        let v = TemplateValues {
+            stem: "...",
+            ..values(4, None, None)
+        };
+        assert_eq!(render("{stem}", &v), "005");
+    }
+
+    #[test]
+    fn disambiguate_names() {
+        let disambiguate = |names: &[&str]| {
+            let mut names: Vec<String> = names.iter().map(|&name| name.into()).collect();
+            super::disambiguate(&mut names);
+            names
+        };
+
+        assert_eq!(disambiguate(&["1", "2", "3"]), ["1", "2", "3"]);
+        assert_eq!(
+            disambiguate(&["1", "2", "2", "2", "3"]),
+            ["1", "2", "2-2", "2-3", "3"]
+        );
+        // suffixed names do not take the name of a later page
+        assert_eq!(disambiguate(&["2", "2", "2-2"]), ["2", "2-3", "2-2"]);
+        assert_eq!(disambiguate(&["Intro", "intro"]), ["Intro", "intro-2"]);
+    }
+}
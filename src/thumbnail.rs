@@ -0,0 +1,169 @@
+// Copyright (C) 2024 Adrien Bustany <adrien@bustany.org>
+
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+
+/// Target size of a thumbnail, in pixels at a density of 1. How pages are
+/// fitted into it depends on the [`ThumbnailFit`].
+///
+/// Parsed from and displayed as `WIDTHxHEIGHT`, or as a single number for
+/// square sizes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct ThumbnailSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ThumbnailSize {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { width, height }
+    }
+
+    /// Returns the size multiplied by a pixel density.
+    pub(crate) fn scaled(self, density: u32) -> Self {
+        Self::new(
+            self.width.saturating_mul(density),
+            self.height.saturating_mul(density),
+        )
+    }
+}
+
+impl From<u32> for ThumbnailSize {
+    fn from(size: u32) -> Self {
+        Self::new(size, size)
+    }
+}
+
+impl FromStr for ThumbnailSize {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parse = |v: &str| {
+            let v = v.trim();
+            match v.parse::<u32>() {
+                Ok(0) => bail!("thumbnail size must not be zero"),
+                Ok(v) => Ok(v),
+                Err(_) => Err(anyhow!("invalid thumbnail size \"{}\"", v)),
+            }
+        };
+
+        match s.split_once('x') {
+            Some((width, height)) => Ok(Self::new(parse(width)?, parse(height)?)),
+            None => parse(s).map(Self::from),
+        }
+    }
+}
+
+impl fmt::Display for ThumbnailSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.width == self.height {
+            write!(f, "{}", self.width)
+        } else {
+            write!(f, "{}x{}", self.width, self.height)
+        }
+    }
+}
+
+impl From<ThumbnailSize> for String {
+    fn from(size: ThumbnailSize) -> Self {
+        size.to_string()
+    }
+}
+
+impl TryFrom<String> for ThumbnailSize {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+/// How pages are fitted into the [`ThumbnailSize`], for pages whose aspect
+/// ratio differs from it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ThumbnailFit {
+    /// Scale the page to fit within the size, keeping its aspect ratio. The
+    /// thumbnail is smaller than the size along one side.
+    #[default]
+    Inside,
+    /// Scale the page to fit within the size, and pad it on both sides to
+    /// get a thumbnail of exactly that size.
+    Contain,
+    /// Scale the page to cover the size, and crop it on both sides to get a
+    /// thumbnail of exactly that size.
+    Cover,
+    /// Scale the page to the width of the size, whatever its height.
+    Width,
+    /// Scale the page to the height of the size, whatever its width.
+    Height,
+}
+
+/// Placement of a page in a thumbnail.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Layout {
+    /// Ratio by which the page gets scaled.
+    pub(crate) scale: f64,
+    /// Position of the top left corner of the scaled page in the thumbnail,
+    /// non-zero when the page is padded or cropped.
+    pub(crate) x: f64,
+    pub(crate) y: f64,
+    /// Dimensions of the thumbnail in pixels.
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+}
+
+impl ThumbnailFit {
+    /// Computes how a page of `page_width` by `page_height` points gets
+    /// placed into a thumbnail of the given size.
+    pub(crate) fn layout(
+        &self,
+        page_width: f64,
+        page_height: f64,
+        size: ThumbnailSize,
+    ) -> Result<Layout> {
+        if !(page_width > 0. && page_height > 0.) {
+            bail!("invalid page size {}x{}", page_width, page_height);
+        }
+
+        let (target_width, target_height) = (f64::from(size.width), f64::from(size.height));
+        let scale = match self {
+            Self::Inside | Self::Contain => {
+                f64::min(target_width / page_width, target_height / page_height)
+            }
+            Self::Cover => f64::max(target_width / page_width, target_height / page_height),
+            Self::Width => target_width / page_width,
+            Self::Height => target_height / page_height,
+        };
+        let scaled = |v: f64| -> Result<u32> {
+            let v = (v * scale).floor().max(1.);
+            if v > f64::from(i32::MAX) {
+                bail!("thumbnail too large");
+            }
+            Ok(v as u32)
+        };
+
+        let layout = match self {
+            Self::Inside | Self::Width | Self::Height => Layout {
+                scale,
+                x: 0.,
+                y: 0.,
+                width: scaled(page_width)?,
+                height: scaled(page_height)?,
+            },
+            // centered
+            Self::Contain | Self::Cover => Layout {
+                scale,
+                x: (target_width - page_width * scale) / 2.,
+                y: (target_height - page_height * scale) / 2.,
+                width: size.width,
+                height: size.height,
+            },
+        };
+
+        Ok(layout)
+    }
+}